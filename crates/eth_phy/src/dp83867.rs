@@ -406,3 +406,72 @@ where
         self.genphy.config_aneg().unwrap();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{SimMdioBus, SimPhy};
+    use crate::Supported;
+
+    fn conf(interface: PhyInterface) -> DP83867Conf {
+        DP83867Conf {
+            rx_id_delay: 0x8,
+            tx_id_delay: 0xA,
+            fifo_depth: 0x3,
+            io_impedance: None,
+            rxctrl_strap_quirk: false,
+            port_mirroring: PortMirroring::KEEP,
+            set_clk_output: false,
+            clk_output_sel: None,
+            sgmii_ref_clk_en: true,
+            interface,
+        }
+    }
+
+    fn supported() -> Supported {
+        Supported {
+            base1000_t_full: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rgmii_delays() {
+        let bus = SimMdioBus::new();
+        let mut strapped = SimPhy::new();
+        // Mistakenly strapped into port mirroring mode 4
+        strapped.set_mmd(DP83867_DEVADDR, 0x6E, 1 << 11);
+        strapped.set_reg(0x10, 1 << 11);
+        bus.add_phy(0, strapped);
+        let genphy = GenPhy::new(0, &bus, supported());
+        Phy::new(&genphy, conf(PhyInterface::RgmiiTxid)).config();
+
+        bus.with_phy(0, |p| {
+            assert_eq!(p.reg(0x10), 0xC000);
+            assert_eq!(p.mmd(DP83867_DEVADDR, 0x32) & 0x3, 0b10);
+            assert_eq!(p.mmd(DP83867_DEVADDR, 0x86), 0xA8);
+            assert_eq!(p.mmd(DP83867_DEVADDR, 0xD3), 0);
+        });
+    }
+
+    #[test]
+    fn sgmii_sequence() {
+        let bus = SimMdioBus::new();
+        let mut phy = SimPhy::new();
+        phy.set_mmd(DP83867_DEVADDR, 0x32, 0x00D3);
+        phy.set_reg(0x16, 0x1234);
+        bus.add_phy(2, phy);
+        let genphy = GenPhy::new(2, &bus, supported());
+        Phy::new(&genphy, conf(PhyInterface::Sgmii)).config();
+
+        bus.with_phy(2, |p| {
+            assert_eq!(p.mmd(DP83867_DEVADDR, 0xD3), 1 << 14);
+            assert_eq!(p.reg(0x00) & 0x1140, 0x1140);
+            assert_eq!(p.reg(0x14), 0x29C0);
+            assert_eq!(p.mmd(DP83867_DEVADDR, 0x32), 0);
+            assert_eq!(p.reg(0x10), 0xF840);
+            assert_eq!(p.reg(0x16), 0);
+            assert_eq!(p.reg(0x1f), 0);
+        });
+    }
+}
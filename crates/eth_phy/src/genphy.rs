@@ -10,7 +10,7 @@ use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
 use tock_registers::registers::InMemoryRegister;
 use tock_registers::{register_bitfields, RegisterLongName};
 
-use super::{Duplex, PhyReadWrite, Speed, Supported};
+use super::{Duplex, LinkStatus, PhyReadWrite, Speed, Supported};
 
 const PHYREG_MASK: u16 = 0x1808;
 
@@ -428,6 +428,41 @@ where
         self.parse_link()
     }
 
+    // Non-blocking counterpart to `startup`, returns None while the link is down or
+    // autonegotiation is still in progress
+    pub fn link_status(&self) -> Option<LinkStatus> {
+        /* Link status is latched low, read a second time to get the current state */
+        let mut bmsr: Reg<T, Bmsr::Register> = Reg::from_read(self, RegNum::Mii(Mii::Bmsr));
+        bmsr.phy_read();
+        if !bmsr
+            .reg()
+            .matches_all(Bmsr::LSTATUS::SET + Bmsr::ANEGCOMPLETE::SET)
+        {
+            return None;
+        }
+
+        let (speed, duplex) = self.parse_link();
+        let pause = duplex == Duplex::Full && self.resolve_rx_pause();
+        Some(LinkStatus {
+            speed,
+            duplex,
+            pause,
+        })
+    }
+
+    /* IEEE 802.3 Annex 28B pause resolution, receive direction only */
+    fn resolve_rx_pause(&self) -> bool {
+        let adv: Reg<T, Advertise::Register> = Reg::from_read(self, RegNum::Mii(Mii::Advertise));
+        let lpa: Reg<T, Advertise::Register> = Reg::from_read(self, RegNum::Mii(Mii::Lpa));
+        let (adv, lpa) = (adv.reg(), lpa.reg());
+
+        if adv.is_set(Advertise::PAUSE_CAP) && lpa.is_set(Advertise::PAUSE_CAP) {
+            return true;
+        }
+        adv.matches_all(Advertise::PAUSE_CAP::SET + Advertise::PAUSE_ASYM::SET)
+            && lpa.is_set(Advertise::PAUSE_ASYM)
+    }
+
     fn update_link(&self) {
         /*
          * Wait if the link is up, and autonegotiation is in progress
@@ -536,3 +571,82 @@ where
         (speed, duplex)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{LinkPartner, SimMdioBus, SimPhy};
+
+    fn gigabit() -> Supported {
+        Supported {
+            base100_t_half: true,
+            base100_t_full: true,
+            base1000_t_full: true,
+            pause: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn detect_scans_down_from_31() {
+        let bus = SimMdioBus::new();
+        bus.add_phy(3, SimPhy::new());
+        bus.add_phy(7, SimPhy::new());
+        assert_eq!(GenPhy::new(3, &bus, gigabit()).addr, 3);
+        let phy = GenPhy::new(0, &bus, gigabit());
+        assert_eq!(phy.addr, 7);
+
+        bus.remove_phy(3);
+        bus.remove_phy(7);
+        assert!(phy.detect(3).is_err());
+    }
+
+    #[test]
+    fn config_advert_reports_changes() {
+        let bus = SimMdioBus::new();
+        bus.add_phy(0, SimPhy::new());
+        let phy = GenPhy::new(0, &bus, gigabit());
+
+        assert_eq!(phy.config_advert(), Ok(true));
+        bus.with_phy(0, |p| {
+            assert_eq!(p.reg(0x04), 0x0581);
+            assert_eq!(p.reg(0x09), 0x0200);
+        });
+        assert_eq!(phy.config_advert(), Ok(false));
+
+        // Every gigabit PHY must flag extended status
+        bus.with_phy(0, |p| p.set_reg(0x01, 0x7809));
+        assert!(phy.config_advert().is_err());
+    }
+
+    #[test]
+    fn parse_link_against_partner() {
+        let bus = SimMdioBus::new();
+        bus.add_phy(0, SimPhy::new());
+        let phy = GenPhy::new(0, &bus, gigabit());
+        phy.config_aneg().unwrap();
+        assert_eq!(phy.link_status(), None);
+
+        let partner = LinkPartner::up_to(Speed::S1000, Duplex::Full, true);
+        bus.with_phy(0, |p| p.set_link_partner(Some(partner)));
+        assert_eq!(phy.startup(), (Speed::S1000, Duplex::Full));
+        assert_eq!(
+            phy.link_status(),
+            Some(LinkStatus {
+                speed: Speed::S1000,
+                duplex: Duplex::Full,
+                pause: true,
+            })
+        );
+
+        // The drop stays latched even though the link came back, and only common modes count
+        let partner = LinkPartner::up_to(Speed::S100, Duplex::Half, false);
+        bus.with_phy(0, |p| {
+            p.set_link_partner(None);
+            p.set_link_partner(Some(partner));
+        });
+        assert_eq!(bus.phy_read(0, 0x01) & 0x0004, 0);
+        assert_eq!(phy.startup(), (Speed::S100, Duplex::Half));
+        assert_eq!(phy.link_status().map(|l| l.pause), Some(false));
+    }
+}
@@ -8,6 +8,8 @@
 
 pub mod dp83867;
 mod genphy;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
 pub use genphy::GenPhy;
 
 pub trait PhyReadWrite {
@@ -46,19 +48,28 @@ pub struct Supported {
     pub base1000_x_full: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Speed {
     S10,
     S100,
     S1000,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Duplex {
     Half,
     Full,
 }
 
+// Result of autonegotiation on an established link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkStatus {
+    pub speed: Speed,
+    pub duplex: Duplex,
+    // Whether received pause frames should be honoured
+    pub pause: bool,
+}
+
 #[derive(PartialEq)]
 pub enum PhyInterface {
     Na,
@@ -106,7 +117,7 @@ mod tests {
 
     #[test]
     fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+        assert!(PhyInterface::RgmiiId.is_rgmii());
+        assert!(!PhyInterface::Sgmii.is_rgmii());
     }
 }
@@ -0,0 +1,281 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+// Simulated MDIO bus for host tests. Each populated address holds the register file of one
+// PHY, with clause 45 registers reached through MmdCtrl/MmdData the way GenPhy accesses them,
+// and autonegotiation resolving instantly against a configurable link partner.
+
+extern crate std;
+
+use core::cell::RefCell;
+use std::vec::Vec;
+
+use super::{Duplex, PhyReadWrite, Speed};
+
+const BMCR: usize = 0x00;
+const BMSR: usize = 0x01;
+const PHYSID1: usize = 0x02;
+const PHYSID2: usize = 0x03;
+const ADVERTISE: usize = 0x04;
+const LPA: usize = 0x05;
+const CTRL1000: usize = 0x09;
+const STAT1000: usize = 0x0a;
+const MMD_CTRL: usize = 0x0d;
+const MMD_DATA: usize = 0x0e;
+const ESTATUS: usize = 0x0f;
+// Vendor control register with self clearing reset/restart bits, e.g. DP83867 CTRL
+const VENDOR_CTRL: usize = 0x1f;
+
+const BMCR_ANRESTART: u16 = 1 << 9;
+const BMCR_ANENABLE: u16 = 1 << 12;
+const BMCR_RESET: u16 = 1 << 15;
+const BMSR_LSTATUS: u16 = 1 << 2;
+const BMSR_ANEGCOMPLETE: u16 = 1 << 5;
+const ADVERTISE_10HALF: u16 = 1 << 5;
+const ADVERTISE_10FULL: u16 = 1 << 6;
+const ADVERTISE_100HALF: u16 = 1 << 7;
+const ADVERTISE_100FULL: u16 = 1 << 8;
+const ADVERTISE_PAUSE_CAP: u16 = 1 << 10;
+const ADVERTISE_PAUSE_ASYM: u16 = 1 << 11;
+const LPA_LPACK: u16 = 1 << 14;
+const LPA_1000HALF: u16 = 1 << 10;
+const LPA_1000FULL: u16 = 1 << 11;
+const MMD_FUNC_MASK: u16 = 0xC000;
+const MMD_FUNC_ADDR: u16 = 0x0000;
+const MMD_FUNC_INCR_RW: u16 = 0x8000;
+const MMD_FUNC_INCR_W: u16 = 0xC000;
+
+// What the far end of the cable advertises
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkPartner {
+    // Advertisement register contents, as they appear in our LPA
+    pub advertise: u16,
+    pub gigabit_half: bool,
+    pub gigabit_full: bool,
+}
+
+impl LinkPartner {
+    // A partner advertising every mode up to and including `speed`/`duplex`
+    pub fn up_to(speed: Speed, duplex: Duplex, pause: bool) -> Self {
+        let full = duplex == Duplex::Full;
+        let mut advertise = ADVERTISE_10HALF | ADVERTISE_10FULL;
+        if speed != Speed::S10 {
+            advertise |= ADVERTISE_100HALF | ADVERTISE_100FULL;
+        }
+        let gigabit = speed == Speed::S1000;
+        // Drop the full duplex mode at the top speed for a half duplex partner
+        if !full {
+            match speed {
+                Speed::S10 => advertise &= !ADVERTISE_10FULL,
+                Speed::S100 => advertise &= !ADVERTISE_100FULL,
+                Speed::S1000 => (),
+            }
+        }
+        if pause {
+            advertise |= ADVERTISE_PAUSE_CAP | ADVERTISE_PAUSE_ASYM;
+        }
+        Self {
+            advertise: advertise | 0x0001,
+            gigabit_half: gigabit,
+            gigabit_full: gigabit && full,
+        }
+    }
+}
+
+// Register file of one PHY
+#[derive(Debug, Clone)]
+pub struct SimPhy {
+    regs: [u16; 32],
+    mmd: Vec<(u16, u16, u16)>,
+    mmd_addr: u16,
+    partner: Option<LinkPartner>,
+    link: bool,
+    latched_down: bool,
+}
+
+impl SimPhy {
+    // Gigabit copper PHY with the identifiers of a DP83867
+    pub fn new() -> Self {
+        let mut regs = [0u16; 32];
+        regs[BMCR] = 0x1140;
+        regs[BMSR] = 0x7909;
+        regs[PHYSID1] = 0x2000;
+        regs[PHYSID2] = 0xA231;
+        regs[ADVERTISE] = 0x01E1;
+        regs[CTRL1000] = 0x0300;
+        regs[ESTATUS] = 0x3000;
+        Self {
+            regs,
+            mmd: Vec::new(),
+            mmd_addr: 0,
+            partner: None,
+            link: false,
+            latched_down: false,
+        }
+    }
+
+    pub fn reg(&self, regnum: usize) -> u16 {
+        self.regs[regnum]
+    }
+
+    pub fn set_reg(&mut self, regnum: usize, value: u16) {
+        self.regs[regnum] = value;
+    }
+
+    pub fn mmd(&self, devad: u16, regnum: u16) -> u16 {
+        if devad == VENDOR_CTRL as u16 && regnum < 0x20 {
+            return self.regs[regnum as usize];
+        }
+        self.mmd
+            .iter()
+            .find(|(d, r, _)| (*d, *r) == (devad, regnum))
+            .map_or(0, |(_, _, v)| *v)
+    }
+
+    pub fn set_mmd(&mut self, devad: u16, regnum: u16, value: u16) {
+        // The vendor MMD aliases the clause 22 registers below 0x20
+        if devad == VENDOR_CTRL as u16 && regnum < 0x20 {
+            self.regs[regnum as usize] = value;
+            return;
+        }
+        match self
+            .mmd
+            .iter_mut()
+            .find(|(d, r, _)| (*d, *r) == (devad, regnum))
+        {
+            Some(entry) => entry.2 = value,
+            None => self.mmd.push((devad, regnum, value)),
+        }
+    }
+
+    pub fn link_partner(&self) -> Option<LinkPartner> {
+        self.partner
+    }
+
+    // Plug in a partner, or unplug the cable with None
+    pub fn set_link_partner(&mut self, partner: Option<LinkPartner>) {
+        self.partner = partner;
+        self.autonegotiate();
+    }
+
+    fn autonegotiate(&mut self) {
+        let was_up = self.link;
+        match self.partner {
+            Some(partner) if self.regs[BMCR] & BMCR_ANENABLE != 0 => {
+                self.regs[LPA] = partner.advertise | LPA_LPACK;
+                self.regs[STAT1000] = (partner.gigabit_half as u16 * LPA_1000HALF)
+                    | (partner.gigabit_full as u16 * LPA_1000FULL);
+                self.regs[BMSR] |= BMSR_ANEGCOMPLETE;
+                self.link = true;
+            }
+            _ => {
+                self.regs[LPA] = 0;
+                self.regs[STAT1000] = 0;
+                self.regs[BMSR] &= !BMSR_ANEGCOMPLETE;
+                self.link = false;
+            }
+        }
+        if was_up && !self.link {
+            self.latched_down = true;
+        }
+    }
+
+    fn read(&mut self, regnum: usize) -> u16 {
+        match regnum {
+            BMSR => {
+                // Link status latches low until read
+                let up = self.link && !self.latched_down;
+                self.latched_down = false;
+                (self.regs[BMSR] & !BMSR_LSTATUS) | (up as u16 * BMSR_LSTATUS)
+            }
+            MMD_DATA => match self.regs[MMD_CTRL] & MMD_FUNC_MASK {
+                MMD_FUNC_ADDR => self.mmd_addr,
+                func => {
+                    let devad = self.regs[MMD_CTRL] & !MMD_FUNC_MASK;
+                    let value = self.mmd(devad, self.mmd_addr);
+                    if func == MMD_FUNC_INCR_RW {
+                        self.mmd_addr = self.mmd_addr.wrapping_add(1);
+                    }
+                    value
+                }
+            },
+            _ => self.regs[regnum],
+        }
+    }
+
+    fn write(&mut self, regnum: usize, value: u16) {
+        match regnum {
+            BMCR => {
+                let enabling = value & BMCR_ANENABLE != 0 && self.regs[BMCR] & BMCR_ANENABLE == 0;
+                self.regs[BMCR] = value & !(BMCR_RESET | BMCR_ANRESTART);
+                if enabling || value & (BMCR_ANRESTART | BMCR_RESET) != 0 {
+                    self.autonegotiate();
+                }
+            }
+            BMSR | PHYSID1 | PHYSID2 | LPA | STAT1000 | ESTATUS => (),
+            MMD_DATA => match self.regs[MMD_CTRL] & MMD_FUNC_MASK {
+                MMD_FUNC_ADDR => self.mmd_addr = value,
+                func => {
+                    let devad = self.regs[MMD_CTRL] & !MMD_FUNC_MASK;
+                    self.set_mmd(devad, self.mmd_addr, value);
+                    if func == MMD_FUNC_INCR_RW || func == MMD_FUNC_INCR_W {
+                        self.mmd_addr = self.mmd_addr.wrapping_add(1);
+                    }
+                }
+            },
+            VENDOR_CTRL => self.regs[VENDOR_CTRL] = value & !0xC000,
+            _ => self.regs[regnum] = value,
+        }
+    }
+}
+
+impl Default for SimPhy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Management bus with up to 32 PHYs. Unpopulated addresses read back 0xFFFF.
+#[derive(Debug, Default)]
+pub struct SimMdioBus {
+    phys: RefCell<[Option<SimPhy>; 32]>,
+}
+
+impl SimMdioBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_phy(&self, addr: u32, phy: SimPhy) {
+        self.phys.borrow_mut()[addr as usize] = Some(phy);
+    }
+
+    pub fn remove_phy(&self, addr: u32) -> Option<SimPhy> {
+        self.phys.borrow_mut()[addr as usize].take()
+    }
+
+    // Run `f` against the PHY at `addr` to inspect or change it
+    pub fn with_phy<R>(&self, addr: u32, f: impl FnOnce(&mut SimPhy) -> R) -> R {
+        f(self.phys.borrow_mut()[addr as usize]
+            .as_mut()
+            .expect("no PHY at this address"))
+    }
+}
+
+impl PhyReadWrite for SimMdioBus {
+    fn phy_write(&self, phy_addr: u32, regnum: u32, data: u16) {
+        if let Some(phy) = self.phys.borrow_mut()[phy_addr as usize & 0x1F].as_mut() {
+            phy.write(regnum as usize & 0x1F, data);
+        }
+    }
+
+    fn phy_read(&self, phy_addr: u32, regnum: u32) -> u16 {
+        match self.phys.borrow_mut()[phy_addr as usize & 0x1F].as_mut() {
+            Some(phy) => phy.read(regnum as usize & 0x1F),
+            None => 0xFFFF,
+        }
+    }
+}
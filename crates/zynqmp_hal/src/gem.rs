@@ -4,6 +4,7 @@
 // SPDX-License-Identifier: BSD-2-Clause
 //
 
+use core::mem::ManuallyDrop;
 use core::ops::Deref;
 use tock_registers::{
     fields::FieldValue,
@@ -16,9 +17,61 @@ use zynqmp_pac::gem::*;
 
 use eth_phy::{Duplex, PhyReadWrite, Speed};
 
-pub struct Device<S> {
+mod cache;
+mod capabilities;
+mod checksum;
+mod config;
+mod driver;
+#[cfg(feature = "embassy")]
+mod embassy;
+mod filter;
+mod interrupts;
+mod link;
+mod pool;
+mod ring;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
+#[cfg(feature = "smoltcp")]
+mod smoltcp;
+mod status;
+mod suspend;
+mod translate;
+mod vlan;
+
+#[cfg(target_arch = "aarch64")]
+pub use cache::Aarch64Cache;
+pub use cache::{CacheOps, Coherent};
+pub use capabilities::{DmaBusWidth, GemCapabilities};
+pub use checksum::{ChecksumOffload, RxChecksum};
+pub use config::{BurstLength, GemConfig};
+pub use driver::{
+    Driver, DriverStats, RxChain, RxFrame, RxToken, SuspendedDriver, TxInFlight, TxToken, FCS_LEN,
+    MAX_SG_PARTS,
+};
+#[cfg(feature = "embassy")]
+pub use embassy::{AsyncDriver, State};
+pub use filter::{multicast_hash_index, Filterable, EXTRA_ADDRESS_FILTERS};
+pub use interrupts::{GemInterrupts, InterruptCoalescing};
+pub use link::{LinkEvent, LinkSupervisor};
+pub use pool::{DmaPool, DMA_ALIGN};
+pub use ring::{
+    RxDescriptor, RxRing, TxCompletion, TxDescriptor, TxError, TxFrameId, TxRing, RX_BUF_UNIT,
+    TX_COMPLETION_QUEUE,
+};
+pub use status::{RecvStatus, RxFrameInfo, TxStatus};
+pub use suspend::{SavedState, WakeOnLan};
+pub use translate::DmaTranslation;
+pub use vlan::{VlanTag, TPID_QINQ, TPID_VLAN};
+
+// How long `Device::shutdown` and `Device::suspend` wait for queued frames to go out, and
+// `Device::halt_tx` for the frame in progress, in reads of transmit_status
+pub const TX_IDLE_POLLS: u32 = 10_000_000;
+
+// `I` is the controller instance, see `Instance`. Dropping a device in a state where the DMA
+// may be running stops it, see `Device::leak` to keep it going.
+pub struct Device<S: DeviceState, I = AnyGem> {
     ptr: *mut RegisterBlock,
-    phantom: PhantomData<S>,
+    phantom: PhantomData<(S, I)>,
 }
 
 pub struct MacAddress([u8; 6]);
@@ -27,24 +80,91 @@ pub struct Reset;
 pub struct PhyReady;
 pub struct Config;
 pub struct Running;
+// Stopped for a power down, see `Device::suspend`
+pub struct Suspended;
+
+pub trait DeviceState {
+    // Whether the DMA may be accessing memory, so dropping the device has to stop it
+    const DMA_ACTIVE: bool = false;
+}
+
+impl DeviceState for Reset {}
+impl DeviceState for PhyReady {}
+impl DeviceState for Config {}
+impl DeviceState for Running {
+    const DMA_ACTIVE: bool = true;
+}
+// Receive stays enabled with wake-on-LAN armed
+impl DeviceState for Suspended {
+    const DMA_ACTIVE: bool = true;
+}
+
+// Controller instances, so the one a driver is bound to is part of its type
+pub trait Instance {
+    type Peripheral;
+    const PTR: *mut RegisterBlock;
+}
+
+pub enum Gem0 {}
+pub enum Gem1 {}
+pub enum Gem2 {}
+pub enum Gem3 {}
+// Instance only known at runtime, for devices created from a raw pointer
+pub enum AnyGem {}
+
+impl Instance for Gem0 {
+    type Peripheral = GEM0;
+    const PTR: *mut RegisterBlock = GEM0::PTR;
+}
+
+impl Instance for Gem1 {
+    type Peripheral = GEM1;
+    const PTR: *mut RegisterBlock = GEM1::PTR;
+}
+
+impl Instance for Gem2 {
+    type Peripheral = GEM2;
+    const PTR: *mut RegisterBlock = GEM2::PTR;
+}
+
+impl Instance for Gem3 {
+    type Peripheral = GEM3;
+    const PTR: *mut RegisterBlock = GEM3::PTR;
+}
 
 impl Device<Reset> {
-    pub fn new(ptr: *mut RegisterBlock) -> Self {
+    /// # Safety
+    ///
+    /// `ptr` must point to a GEM register block that is not owned elsewhere, e.g. by a
+    /// `Device` created from a `zynqmp_pac::Peripherals` token.
+    pub unsafe fn new(ptr: *mut RegisterBlock) -> Self {
         Self {
             ptr,
             phantom: PhantomData,
         }
     }
+}
 
-    pub fn init(&self) -> Device<PhyReady> {
-        self.reset_dev();
-        self.set_defaults();
-        // TODO: I/O Configuration. Clocks and MIO. Can defer if we assume bootloader has done this.
-        Device {
-            ptr: self.ptr,
+impl<I: Instance> Device<Reset, I> {
+    // Takes ownership of the instance token from `zynqmp_pac::Peripherals::take`
+    pub fn from_peripheral(_peripheral: I::Peripheral) -> Self {
+        Self {
+            ptr: I::PTR,
             phantom: PhantomData,
         }
     }
+}
+
+impl<I> Device<Reset, I> {
+    pub fn init(self, config: GemConfig) -> Result<Device<PhyReady, I>, &'static str> {
+        let caps = self.capabilities();
+        config.validate(&caps)?;
+
+        self.reset_dev();
+        self.apply_config(&config, &caps);
+        // TODO: I/O Configuration. Clocks and MIO. Can defer if we assume bootloader has done this.
+        Ok(self.transition())
+    }
 
     fn reset_dev(&self) {
         // Disable interrupts
@@ -62,6 +182,7 @@ impl Device<Reset> {
 
         self.tx_bd_control.set(0);
         self.rx_bd_control.set(0);
+        self.int_moderation.set(0);
 
         // Clear hash registers for MAC address
         self.hash_bottom.set(0);
@@ -70,44 +191,65 @@ impl Device<Reset> {
         // TODO: Clear stats registers? 0x100-0x1B4
     }
 
-    fn set_defaults(&self) {
-        let net_cfg = network_config::NO_BROADCAST::CLEAR
-            + network_config::DATA_BUS_WIDTH.val(1)
-            + network_config::RECEIVE_CHECKSUM_OFFLOAD_ENABLE::SET
+    fn apply_config(&self, config: &GemConfig, caps: &GemCapabilities) {
+        // Checksum offload needs the packet buffer to hold the whole frame
+        let checksum_offload = config.checksum_offload_enabled(caps) as u32;
+
+        let net_cfg = network_config::NO_BROADCAST.val(config.no_broadcast as u32)
+            + network_config::COPY_ALL_FRAMES.val(config.promiscuous as u32)
+            + network_config::FCS_REMOVE.val(config.fcs_remove as u32)
+            + network_config::RECEIVE_BUFFER_OFFSET.val(config.rx_buf_offset as u32)
+            + network_config::DATA_BUS_WIDTH.val(caps.dma_bus_width.config_value())
+            + network_config::RECEIVE_CHECKSUM_OFFLOAD_ENABLE.val(checksum_offload)
             + network_config::PAUSE_ENABLE::CLEAR;
 
-        // TODO: FCS_REMOVE?
         // TODO: multicast_hash_en?
 
         // Modify here and no clear in the reset function to avoid figuring out MDC clock dividor
         self.network_config.modify(net_cfg);
 
-        // TODO: Enable promiscuous mode here? Leave up to user?
-
-        // 1600 bytes for RX buffer
-        // Full size for RX and TX packet buffer sizes
-        // INCR16 AXI Burst size for higher performance
-        let dma_cfg = dma_config::RX_BUF_SIZE.val(25u32)
+        // Packet buffer sizes were validated against the capabilities in init
+        let dma_cfg = dma_config::RX_BUF_SIZE.val((config.rx_buf_size / RX_BUF_UNIT) as u32)
             + dma_config::TX_BD_EXTENDED_MODE_EN::CLEAR
             + dma_config::RX_BD_EXTENDED_MODE_EN::CLEAR
             + dma_config::DMA_ADDR_BUS_WIDTH_1::CLEAR
-            + dma_config::RX_PBUF_SIZE::PBUF_32KB
-            + dma_config::TX_PBUF_SIZE::SET
-            // + dma_config::TX_PBUF_TCP_EN::SET
-            + dma_config::ENDIAN_SWAP_PACKET::CLEAR
-            + dma_config::AMBA_BURST_LENGTH.val(4u32);
+            + dma_config::RX_PBUF_SIZE.val(config.rx_pbuf_config(caps).unwrap_or(0))
+            + dma_config::TX_PBUF_SIZE.val(config.tx_pbuf_config(caps).unwrap_or(0))
+            + dma_config::TX_PBUF_TCP_EN.val(checksum_offload)
+            + dma_config::ENDIAN_SWAP_PACKET.val(config.endian_swap_packet as u32)
+            + dma_config::ENDIAN_SWAP_MANAGEMENT.val(config.endian_swap_management as u32)
+            + dma_config::AMBA_BURST_LENGTH.val(config.burst_length as u32)
+            + dma_config::FORCE_MAX_AMBA_BURST_TX.val(config.force_max_burst as u32)
+            + dma_config::FORCE_MAX_AMBA_BURST_RX.val(config.force_max_burst as u32);
 
         self.dma_config.write(dma_cfg);
 
+        // Thresholds were validated against the packet buffer sizes in init
+        let tx_cut_through = config.tx_cut_through_config(caps).unwrap_or(None);
+        self.pbuf_txcutthru.write(
+            pbuf_txcutthru::DMA_TX_CUTTHRU.val(tx_cut_through.is_some() as u32)
+                + pbuf_txcutthru::DMA_TX_CUTTHRU_THRESHOLD.val(tx_cut_through.unwrap_or(0)),
+        );
+        let rx_cut_through = config.rx_cut_through_config(caps).unwrap_or(None);
+        self.pbuf_rxcutthru.write(
+            pbuf_rxcutthru::DMA_RX_CUTTHRU.val(rx_cut_through.is_some() as u32)
+                + pbuf_rxcutthru::DMA_RX_CUTTHRU_THRESHOLD.val(rx_cut_through.unwrap_or(0)),
+        );
+        if let Some((reads, writes)) = config.axi_pipeline {
+            self.axi_max_pipeline.write(
+                axi_max_pipeline::AR2R_MAX_PIPELINE.val(reads as u32)
+                    + axi_max_pipeline::AW2W_MAX_PIPELINE.val(writes as u32),
+            );
+        }
+
         self.network_control
             .modify(network_control::MAN_PORT_EN::SET);
 
         // TODO: Disable second priority queue? Set *_q1_ptr to addresses of a single descriptor which set the wrap bit
 
-        self.int_enable.write(
-            int_enable::ENABLE_RECEIVE_COMPLETE_INTERRUPT::SET
-                + int_enable::ENABLE_TRANSMIT_COMPLETE_INTERRUPT::SET,
-        );
+        self.int_moderation
+            .write(config.coalescing.register_value());
+        self.int_enable.set(config.interrupts.bits());
     }
 }
 
@@ -149,16 +291,13 @@ impl From<(u32, u16)> for MacAddress {
     }
 }
 
-#[derive(Debug)]
-pub enum RecvStatus {
-    FrameReceived,
-    RespNotOk,
-    FifoOverflow,
-    UnavailableBuffer,
-    Unknown,
+// MDIO access to the PHYs attached to the management port. Management frames only touch
+// phy_management and network_status, so a handle may be used alongside a running device.
+pub struct Mdio {
+    ptr: *mut RegisterBlock,
 }
 
-impl PhyReadWrite for Device<PhyReady> {
+impl PhyReadWrite for Mdio {
     fn phy_write(&self, phy_addr: u32, regnum: u32, data: u16) {
         self.phy_setup_op(phy_addr, regnum, phy_management::OPERATION::Write, data);
     }
@@ -171,7 +310,7 @@ impl PhyReadWrite for Device<PhyReady> {
     }
 }
 
-impl Device<PhyReady> {
+impl Mdio {
     fn phy_setup_op(
         &self,
         phy_addr: u32,
@@ -204,22 +343,69 @@ impl Device<PhyReady> {
             core::hint::spin_loop();
         }
     }
+}
+
+impl Deref for Mdio {
+    type Target = RegisterBlock;
 
-    pub fn phy_complete(&self) -> Device<Config> {
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.ptr }
+    }
+}
+
+impl<I> PhyReadWrite for Device<PhyReady, I> {
+    fn phy_write(&self, phy_addr: u32, regnum: u32, data: u16) {
+        self.mdio().phy_write(phy_addr, regnum, data);
+    }
+
+    fn phy_read(&self, phy_addr: u32, regnum: u32) -> u16 {
+        self.mdio().phy_read(phy_addr, regnum)
+    }
+}
+
+impl<I> Device<PhyReady, I> {
+    fn mdio(&self) -> Mdio {
+        Mdio { ptr: self.ptr }
+    }
+
+    #[must_use]
+    pub fn phy_complete(self) -> Device<Config, I> {
+        self.transition()
+    }
+}
+
+impl<S: DeviceState, I> Device<S, I> {
+    // The same controller in another state, without dropping this one
+    fn transition<T: DeviceState>(self) -> Device<T, I> {
+        let dev = ManuallyDrop::new(self);
         Device {
-            ptr: self.ptr,
+            ptr: dev.ptr,
             phantom: PhantomData,
         }
     }
-}
 
-impl<S> Device<S> {
+    // Only takes effect with transmit disabled or halted
+    fn write_tx_queue(&self, desc: usize) {
+        self.transmit_q_ptr
+            .write(transmit_q_ptr::DMA_TX_Q_PTR.val(desc as u32));
+        self.upper_tx_q_base_addr
+            .write(upper_tx_q_base_addr::UPPER_TX_Q_BASE_ADDR.val((desc as u64 >> 32) as u32));
+    }
+
+    // Only takes effect with receive disabled
+    fn write_rx_queue(&self, desc: usize) {
+        self.receive_q_ptr
+            .write(receive_q_ptr::DMA_RX_Q_PTR.val(desc as u32));
+        self.upper_rx_q_base_addr
+            .write(upper_rx_q_base_addr::UPPER_RX_Q_BASE_ADDR.val((desc as u64 >> 32) as u32));
+    }
+
     fn ptr(&self) -> *mut RegisterBlock {
         self.ptr
     }
 }
 
-impl Device<Config> {
+impl<I> Device<Config, I> {
     pub fn set_speed(&self, speed: Speed) {
         match speed {
             Speed::S1000 => self
@@ -243,46 +429,34 @@ impl Device<Config> {
         }
     }
 
-    pub fn enable_promiscuous_mode(&self) {
+    // Honour received pause frames by pausing transmission
+    pub fn set_pause(&self, enable: bool) {
         self.network_config
-            .modify(network_config::COPY_ALL_FRAMES::SET);
+            .modify(network_config::PAUSE_ENABLE.val(enable as u32));
     }
 
-    pub fn disable_promiscuous_mode(&self) {
-        self.network_config
-            .modify(network_config::COPY_ALL_FRAMES::CLEAR);
+    pub fn set_tx_desc(&self, desc: usize) {
+        self.write_tx_queue(desc);
     }
 
-    pub fn set_mac_address(&self, mac: MacAddress) {
-        self.spec_add1_bottom
-            .write(spec_add1_bottom::ADDRESS.val(mac.get_bottom()));
-        self.spec_add1_top
-            .write(spec_add1_top::ADDRESS.val(mac.get_top().into()));
-    }
-
-    pub fn split_mac_address(&self) -> (u32, u32) {
-        let bottom = self.spec_add1_bottom.read(spec_add1_bottom::ADDRESS);
-        let top = self.spec_add1_top.read(spec_add1_top::ADDRESS);
-        (bottom, top)
-    }
-
-    pub fn set_tx_desc(&self, desc: u32) {
-        self.transmit_q_ptr
-            .write(transmit_q_ptr::DMA_TX_Q_PTR.val(desc));
-        self.upper_tx_q_base_addr
-            .write(upper_tx_q_base_addr::UPPER_TX_Q_BASE_ADDR.val(0));
-    }
-
-    pub fn set_tx_q1_desc(&self, desc: u32) {
+    pub fn set_tx_q1_desc(&self, desc: u32) -> Result<(), &'static str> {
+        if self.capabilities().priority_queues < 2 {
+            return Err("GEM has no priority queue 1");
+        }
         self.transmit_q1_ptr
             .write(transmit_q1_ptr::DMA_TX_Q_PTR.val(desc));
+        Ok(())
     }
 
-    pub fn set_rx_desc(&self, desc: u32) {
-        self.receive_q_ptr
-            .write(receive_q_ptr::DMA_RX_Q_PTR.val(desc));
-        self.upper_rx_q_base_addr
-            .write(upper_rx_q_base_addr::UPPER_RX_Q_BASE_ADDR.val(0));
+    // Bits 31:28 the controller forces in buffer addresses, see `DmaTranslation::OrMask`
+    pub fn set_dma_or_mask(&self, value: u32, enable: u32) {
+        self.dma_addr_or_mask.write(
+            dma_addr_or_mask::MASK_VALUE.val(value) + dma_addr_or_mask::MASK_ENABLE.val(enable),
+        );
+    }
+
+    pub fn set_rx_desc(&self, desc: usize) {
+        self.write_rx_queue(desc);
     }
 
     fn enable_tx(&self) {
@@ -295,17 +469,27 @@ impl Device<Config> {
             .modify(network_control::ENABLE_RECEIVE::SET);
     }
 
-    pub fn run(&self) -> Device<Running> {
+    #[must_use]
+    pub fn run(self) -> Device<Running, I> {
         self.enable_tx();
         self.enable_rx();
-        Device {
-            ptr: self.ptr,
-            phantom: PhantomData,
-        }
+        self.transition()
+    }
+
+    // Back to the state after reset, to be initialised again
+    #[must_use]
+    pub fn reset(self) -> Device<Reset, I> {
+        let dev: Device<Reset, I> = self.transition();
+        dev.reset_dev();
+        dev
     }
 }
 
-impl Device<Running> {
+impl<I> Device<Running, I> {
+    pub fn mdio(&self) -> Mdio {
+        Mdio { ptr: self.ptr }
+    }
+
     pub fn clear_all_interrupts(&self) {
         self.int_status.set(0xFFFFFFFF);
     }
@@ -330,30 +514,18 @@ impl Device<Running> {
         self.transmit_q_ptr.get()
     }
 
-    pub fn get_transmit_status(&self) -> u32 {
-        let val = self.transmit_status.get();
-        self.transmit_status.set(val);
-        val
+    // Every condition latched since the last call, which are cleared
+    pub fn get_transmit_status(&self) -> TxStatus {
+        let status = TxStatus::from_bits_truncate(self.transmit_status.get());
+        self.transmit_status.set(status.bits());
+        status
     }
 
+    // Every condition latched since the last call, which are cleared
     pub fn get_receive_status(&self) -> RecvStatus {
-        let val = self.receive_status.extract();
-        let bits = val.get();
-
-        // Clear status
-        self.receive_status.set(bits);
-
-        if val.is_set(receive_status::FRAME_RECEIVED) {
-            RecvStatus::FrameReceived
-        } else if val.is_set(receive_status::RESP_NOT_OK) {
-            RecvStatus::RespNotOk
-        } else if val.is_set(receive_status::RECEIVE_OVERRUN) {
-            RecvStatus::FifoOverflow
-        } else if val.is_set(receive_status::BUFFER_NOT_AVAILABLE) {
-            RecvStatus::UnavailableBuffer
-        } else {
-            RecvStatus::Unknown
-        }
+        let status = RecvStatus::from_bits_truncate(self.receive_status.get());
+        self.receive_status.set(status.bits());
+        status
     }
 
     pub fn transmit(&self) {
@@ -364,17 +536,101 @@ impl Device<Running> {
             core::hint::spin_loop();
         }
 
+        self.start_tx();
+    }
+
+    // Stop transmitting after the frame in progress and wait for the DMA to go idle, see
+    // `TX_IDLE_POLLS`
+    pub fn halt_tx(&self) -> Result<(), &'static str> {
+        self.network_control
+            .modify(network_control::TX_HALT_PCLK::SET);
+        match self.wait_tx_idle() {
+            true => Ok(()),
+            false => Err("Transmitter did not halt"),
+        }
+    }
+
+    // Point a halted transmitter at the start of a descriptor ring and start it again
+    pub(crate) fn restart_tx(&self, desc: usize) {
+        self.write_tx_queue(desc);
+        self.start_tx();
+    }
+
+    // Drop the frame at the head of the RX packet buffer, e.g. one left waiting for a descriptor
+    pub fn flush_rx_packet(&self) {
+        self.network_control
+            .modify(network_control::FLUSH_RX_PKT_PCLK::SET);
+    }
+
+    // Start reception over at the start of a descriptor ring, e.g. once the DMA stopped on a
+    // bus error
+    pub(crate) fn restart_rx(&self, desc: usize) {
+        self.disable_rx();
+        self.write_rx_queue(desc);
+        self.network_control
+            .modify(network_control::ENABLE_RECEIVE::SET);
+    }
+
+    pub(crate) fn start_tx(&self) {
         self.network_control
             .modify(network_control::TX_START_PCLK::SET);
     }
 
-    pub fn stop(&self) -> Device<Config> {
+    // Stop both directions, let `f` reprogram the controller and start it again, keeping this
+    // device in place
+    pub(crate) fn reconfigure<R, F>(&mut self, f: F) -> R
+    where
+        F: FnOnce(&Device<Config, I>) -> R,
+    {
         self.disable_tx();
         self.disable_rx();
-        Device {
+        let dev = Device {
             ptr: self.ptr,
             phantom: PhantomData,
+        };
+        let result = f(&dev);
+        dev.enable_tx();
+        dev.enable_rx();
+        result
+    }
+
+    // Stop both directions straight away, abandoning any frame in progress
+    #[must_use]
+    pub fn stop(self) -> Device<Config, I> {
+        self.disable_tx();
+        self.disable_rx();
+        self.transition()
+    }
+
+    // Let the frames already queued go out, then stop both directions and mask every interrupt.
+    // The device is handed back untouched if transmission does not finish, `stop` it instead.
+    pub fn shutdown(self) -> Result<Device<Config, I>, Self> {
+        if !self.wait_tx_idle() {
+            return Err(self);
         }
+        self.int_disable.set(0xFFFF_FFFF);
+        Ok(self.stop())
+    }
+
+    // Wait for the DMA to send what is queued and go idle, for at most `TX_IDLE_POLLS` reads
+    // of transmit_status
+    pub(crate) fn wait_tx_idle(&self) -> bool {
+        for _ in 0..TX_IDLE_POLLS {
+            if !self
+                .transmit_status
+                .matches_all(transmit_status::TRANSMIT_GO::SET)
+            {
+                return true;
+            }
+            core::hint::spin_loop();
+        }
+        false
+    }
+
+    // Give the device up with the DMA still running, for rings and buffers which live for the
+    // rest of the program
+    pub fn leak(self) {
+        core::mem::forget(self);
     }
 
     fn disable_tx(&self) {
@@ -386,18 +642,52 @@ impl Device<Running> {
         self.network_control
             .modify(network_control::ENABLE_RECEIVE::CLEAR);
     }
-
-    pub fn mac_address(&self) -> MacAddress {
-        let bottom = self.spec_add1_bottom.read(spec_add1_bottom::ADDRESS);
-        let top = self.spec_add1_top.read(spec_add1_top::ADDRESS) as u16;
-        MacAddress::from((bottom, top))
-    }
 }
 
-impl<S> Deref for Device<S> {
+impl<S: DeviceState, I> Deref for Device<S, I> {
     type Target = RegisterBlock;
 
     fn deref(&self) -> &Self::Target {
         unsafe { &*self.ptr() }
     }
 }
+
+impl<S: DeviceState, I> Drop for Device<S, I> {
+    // Hardware must never write to memory which may be freed once the device is gone
+    fn drop(&mut self) {
+        if S::DMA_ACTIVE {
+            self.int_disable.set(0xFFFF_FFFF);
+            self.network_control.modify(
+                network_control::ENABLE_RECEIVE::CLEAR + network_control::ENABLE_TRANSMIT::CLEAR,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use zynqmp_pac::Peripherals;
+
+    #[test]
+    fn devices_bound_to_their_instance() {
+        // Safety: the devices are dropped in reset, so their registers are never accessed
+        let p = unsafe { Peripherals::steal() };
+        assert_eq!(
+            Device::<Reset, Gem0>::from_peripheral(p.GEM0).ptr(),
+            GEM0::PTR
+        );
+        assert_eq!(
+            Device::<Reset, Gem1>::from_peripheral(p.GEM1).ptr(),
+            GEM1::PTR
+        );
+        assert_eq!(
+            Device::<Reset, Gem2>::from_peripheral(p.GEM2).ptr(),
+            GEM2::PTR
+        );
+        assert_eq!(
+            Device::<Reset, Gem3>::from_peripheral(p.GEM3).ptr(),
+            GEM3::PTR
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+// Cache maintenance for packet buffers shared with the DMA. Without hardware coherency the
+// rings call these whenever a buffer changes hands between the CPU and the controller.
+// Descriptors are never maintained: eight of them share a cache line, and cleaning one would
+// write stale copies of its neighbours over what the controller wrote to them.
+pub trait CacheOps {
+    // Write back cached data in the range, so the controller reads what the CPU wrote
+    fn clean(&self, addr: usize, len: usize);
+
+    // Write back and drop cached data in the range, so the CPU next reads what the controller
+    // wrote
+    fn clean_invalidate(&self, addr: usize, len: usize);
+}
+
+// For memory the controller accesses coherently, or which is mapped uncached
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Coherent;
+
+impl CacheOps for Coherent {
+    fn clean(&self, _addr: usize, _len: usize) {}
+
+    fn clean_invalidate(&self, _addr: usize, _len: usize) {}
+}
+
+// Maintenance by virtual address to the point of coherency, with `dc cvac` and `dc civac`
+#[cfg(target_arch = "aarch64")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Aarch64Cache;
+
+#[cfg(target_arch = "aarch64")]
+impl Aarch64Cache {
+    // Smallest data cache line in the system, from CTR_EL0.DminLine
+    fn line_size() -> usize {
+        let ctr: u64;
+        // Safety: reading CTR_EL0 has no side effects
+        unsafe {
+            core::arch::asm!("mrs {}, ctr_el0", out(reg) ctr, options(nomem, nostack, preserves_flags))
+        };
+        4 << ((ctr >> 16) & 0xF)
+    }
+
+    fn lines(addr: usize, len: usize) -> impl Iterator<Item = usize> {
+        let line = Self::line_size();
+        (addr & !(line - 1)..addr + len).step_by(line)
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+impl CacheOps for Aarch64Cache {
+    fn clean(&self, addr: usize, len: usize) {
+        for line in Self::lines(addr, len) {
+            // Safety: cleaning does not change what the CPU reads from the line
+            unsafe {
+                core::arch::asm!("dc cvac, {}", in(reg) line, options(nostack, preserves_flags))
+            };
+        }
+        // Safety: barrier only
+        unsafe { core::arch::asm!("dsb sy", options(nostack, preserves_flags)) };
+    }
+
+    fn clean_invalidate(&self, addr: usize, len: usize) {
+        for line in Self::lines(addr, len) {
+            // Safety: dirty data is written back before the line is dropped, so nothing the
+            // CPU wrote is lost
+            unsafe {
+                core::arch::asm!("dc civac, {}", in(reg) line, options(nostack, preserves_flags))
+            };
+        }
+        // Safety: barrier only
+        unsafe { core::arch::asm!("dsb sy", options(nostack, preserves_flags)) };
+    }
+}
@@ -0,0 +1,164 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use tock_registers::interfaces::Readable;
+
+use zynqmp_pac::gem::*;
+
+use super::{Device, DeviceState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DmaBusWidth {
+    W32,
+    W64,
+    W128,
+}
+
+impl DmaBusWidth {
+    pub fn bits(&self) -> usize {
+        match self {
+            DmaBusWidth::W32 => 32,
+            DmaBusWidth::W64 => 64,
+            DmaBusWidth::W128 => 128,
+        }
+    }
+
+    // Encoding of network_config DATA_BUS_WIDTH
+    pub(crate) fn config_value(&self) -> u32 {
+        match self {
+            DmaBusWidth::W32 => 0,
+            DmaBusWidth::W64 => 1,
+            DmaBusWidth::W128 => 2,
+        }
+    }
+}
+
+// What this instance of the IP was synthesised with, decoded from the design configuration
+// registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GemCapabilities {
+    pub module_id: u16,
+    pub revision: u16,
+    // Including queue 0
+    pub priority_queues: usize,
+    pub dma_bus_width: DmaBusWidth,
+    pub dma_64bit_addressing: bool,
+    // Packet buffer sizes in bytes, 0 when the instance has no packet buffer
+    pub rx_pbuf_size: usize,
+    pub tx_pbuf_size: usize,
+    pub cut_through: bool,
+    pub jumbo_max_length: usize,
+    pub specific_address_filters: usize,
+    pub tsu: bool,
+    pub pcs: bool,
+    pub statistics: bool,
+    pub type1_screeners: usize,
+    pub type2_screeners: usize,
+}
+
+impl GemCapabilities {
+    pub fn has_packet_buffer(&self) -> bool {
+        self.rx_pbuf_size != 0 && self.tx_pbuf_size != 0
+    }
+
+    pub fn has_screeners(&self) -> bool {
+        self.type1_screeners != 0 || self.type2_screeners != 0
+    }
+
+    // Encoding of dma_config RX_PBUF_SIZE for the packet buffer size
+    pub(crate) fn rx_pbuf_config(&self) -> u32 {
+        (self.rx_pbuf_size >> 13).min(3) as u32
+    }
+
+    // Encoding of dma_config TX_PBUF_SIZE for the packet buffer size
+    pub(crate) fn tx_pbuf_config(&self) -> u32 {
+        (self.tx_pbuf_size > 2048) as u32
+    }
+}
+
+impl<S: DeviceState, I> Device<S, I> {
+    pub fn capabilities(&self) -> GemCapabilities {
+        let revision = self.revision_reg.extract();
+        let cfg1 = self.designcfg_debug1.extract();
+        let cfg2 = self.designcfg_debug2.extract();
+        let cfg5 = self.designcfg_debug5.extract();
+        let cfg6 = self.designcfg_debug6.extract();
+        let cfg8 = self.designcfg_debug8.extract();
+
+        // One-hot, widest supported width wins
+        let width = cfg1.read(designcfg_debug1::DMA_BUS_WIDTH);
+        let dma_bus_width = if width & 0b100 != 0 {
+            DmaBusWidth::W128
+        } else if width & 0b010 != 0 {
+            DmaBusWidth::W64
+        } else {
+            DmaBusWidth::W32
+        };
+
+        // The default packet buffer sizes are the largest the instance was built with
+        let rx_pbuf_size = match cfg2.is_set(designcfg_debug2::RX_PKT_BUFFER) {
+            true => 4096 << cfg5.read(designcfg_debug5::RX_PBUF_SIZE_DEF),
+            false => 0,
+        };
+        let tx_pbuf_size = match cfg2.is_set(designcfg_debug2::TX_PKT_BUFFER) {
+            true => 2048 << cfg5.read(designcfg_debug5::TX_PBUF_SIZE_DEF),
+            false => 0,
+        };
+
+        // Bits 15:1 flag the presence of queues 15 to 1
+        let queue_bits = (cfg6.get() >> designcfg_debug6::DMA_PRIORITY_QUEUE1.shift) & 0x7FFF;
+
+        GemCapabilities {
+            module_id: revision.read(revision_reg::MODULE_IDENTIFICATION_NUMBER) as u16,
+            revision: revision.read(revision_reg::MODULE_REVISION) as u16,
+            priority_queues: 1 + queue_bits.count_ones() as usize,
+            dma_bus_width,
+            dma_64bit_addressing: cfg6.is_set(designcfg_debug6::DMA_ADDR_WIDTH_IS_64B),
+            rx_pbuf_size,
+            tx_pbuf_size,
+            cut_through: cfg6.is_set(designcfg_debug6::PBUF_CUTTHRU),
+            jumbo_max_length: cfg2.read(designcfg_debug2::JUMBO_MAX_LENGTH) as usize,
+            specific_address_filters: self
+                .designcfg_debug3
+                .read(designcfg_debug3::NUM_SPEC_ADD_FILTERS)
+                as usize,
+            tsu: cfg5.is_set(designcfg_debug5::TSU),
+            pcs: !cfg1.is_set(designcfg_debug1::NO_PCS),
+            statistics: !cfg1.is_set(designcfg_debug1::NO_STATS),
+            type1_screeners: cfg8.read(designcfg_debug8::NUM_TYPE1_SCREENERS) as usize,
+            type2_screeners: cfg8.read(designcfg_debug8::NUM_TYPE2_SCREENERS) as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gem::sim::GemSim;
+    use crate::gem::GemConfig;
+
+    #[test]
+    fn decode_and_refuse() {
+        let mut sim = GemSim::new();
+        let ptr = sim.ptr();
+        // A refused configuration gives the device up
+        let dev = || unsafe { Device::new(ptr) };
+        let caps = dev().capabilities();
+        assert_eq!(caps.priority_queues, 4);
+        assert_eq!(caps.dma_bus_width, DmaBusWidth::W64);
+        assert_eq!((caps.rx_pbuf_size, caps.tx_pbuf_size), (32768, 4096));
+        assert!(caps.tsu && caps.pcs && caps.has_screeners());
+
+        // Without packet buffers checksum offload is refused, and off by default
+        sim.write_reg(0x284, 0);
+        assert!(dev().init(GemConfig::new().checksum_offload(true)).is_err());
+        assert!(dev().init(GemConfig::new().rx_pbuf_size(8192)).is_err());
+        let dev = dev().init(GemConfig::new()).unwrap();
+        assert!(!dev
+            .network_config
+            .is_set(network_config::RECEIVE_CHECKSUM_OFFLOAD_ENABLE));
+    }
+}
@@ -0,0 +1,343 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use tock_registers::LocalRegisterCopy;
+
+use zynqmp_pac::gem::rx_desc_status;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_IPV6: u16 = 0x86DD;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88A8;
+const IP_PROTO_TCP: u8 = 6;
+const IP_PROTO_UDP: u8 = 17;
+const IPV6_HOP_BY_HOP: u8 = 0;
+const IPV6_ROUTING: u8 = 43;
+const IPV6_FRAGMENT: u8 = 44;
+const IPV6_DEST_OPTIONS: u8 = 60;
+
+// Checksum result the controller reports for a received frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RxChecksum {
+    NotChecked,
+    IpOnly,
+    IpTcp,
+    IpUdp,
+}
+
+impl RxChecksum {
+    // Only meaningful with receive checksum offload enabled, otherwise these bits hold the
+    // type ID match
+    pub(crate) fn from_status(status: LocalRegisterCopy<u32, rx_desc_status::Register>) -> Self {
+        match status.read_as_enum(rx_desc_status::CHECKSUM) {
+            Some(rx_desc_status::CHECKSUM::Value::IpOk) => RxChecksum::IpOnly,
+            Some(rx_desc_status::CHECKSUM::Value::IpTcpOk) => RxChecksum::IpTcp,
+            Some(rx_desc_status::CHECKSUM::Value::IpUdpOk) => RxChecksum::IpUdp,
+            _ => RxChecksum::NotChecked,
+        }
+    }
+}
+
+// Checksum offload state of the controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChecksumOffload {
+    pub rx: bool,
+    pub tx: bool,
+}
+
+// Checksums software still has to handle for the controller offload state, as the
+// `ChecksumCapabilities` of either network stack. Received TCP and UDP checksums are always
+// left to the stack, as those of fragmented datagrams can only be checked once reassembled.
+#[cfg(any(feature = "smoltcp", feature = "embassy"))]
+macro_rules! checksum_capabilities {
+    ($checksum:ident, $caps:ident) => {
+        fn checksum_capabilities(offload: ChecksumOffload) -> $caps {
+            let ipv4 = match (offload.rx, offload.tx) {
+                (true, true) => $checksum::None,
+                (true, false) => $checksum::Tx,
+                (false, true) => $checksum::Rx,
+                (false, false) => $checksum::Both,
+            };
+            let transport = match offload.tx {
+                true => $checksum::Rx,
+                false => $checksum::Both,
+            };
+
+            let mut caps = $caps::default();
+            caps.ipv4 = ipv4;
+            caps.tcp = transport;
+            caps.udp = transport;
+            caps
+        }
+    };
+}
+#[cfg(any(feature = "smoltcp", feature = "embassy"))]
+pub(crate) use checksum_capabilities;
+
+fn sum(data: &[u8], mut acc: u32) -> u32 {
+    let mut chunks = data.chunks_exact(2);
+    for chunk in &mut chunks {
+        acc += u16::from_be_bytes([chunk[0], chunk[1]]) as u32;
+    }
+    if let [last] = chunks.remainder() {
+        acc += (*last as u32) << 8;
+    }
+    acc
+}
+
+fn fold(mut acc: u32) -> u16 {
+    while acc > 0xFFFF {
+        acc = (acc & 0xFFFF) + (acc >> 16);
+    }
+    acc as u16
+}
+
+// A received frame as the buffers it was written to, in order
+struct Frame<S> {
+    segments: S,
+    len: usize,
+}
+
+impl<'b, S: Iterator<Item = &'b [u8]> + Clone> Frame<S> {
+    fn new(segments: S) -> Self {
+        let len = segments.clone().map(|s| s.len()).sum();
+        Self { segments, len }
+    }
+
+    // Copy the bytes at `offset` into `out`, unless the frame ends first
+    fn read(&self, offset: usize, out: &mut [u8]) -> bool {
+        if self.len < offset + out.len() {
+            return false;
+        }
+        let mut pos = 0;
+        for segment in self.segments.clone() {
+            let start = offset.max(pos);
+            let end = (offset + out.len()).min(pos + segment.len());
+            if start < end {
+                out[start - offset..end - offset].copy_from_slice(&segment[start - pos..end - pos]);
+            }
+            pos += segment.len();
+        }
+        true
+    }
+
+    fn be16(&self, offset: usize) -> Option<u16> {
+        let mut bytes = [0; 2];
+        self.read(offset, &mut bytes)
+            .then(|| u16::from_be_bytes(bytes))
+    }
+
+    // Ones' complement sum of `len` bytes at `offset`, carrying on from `acc`
+    fn sum(&self, offset: usize, len: usize, mut acc: u32) -> u32 {
+        let mut pos = 0;
+        // Whether the next byte is the low half of a word
+        let mut odd = false;
+        for segment in self.segments.clone() {
+            let start = offset.max(pos);
+            let end = (offset + len).min(pos + segment.len());
+            if start < end {
+                let mut part = &segment[start - pos..end - pos];
+                if odd {
+                    acc += part[0] as u32;
+                    part = &part[1..];
+                }
+                acc = sum(part, acc);
+                odd = part.len() % 2 == 1;
+            }
+            pos += segment.len();
+        }
+        acc
+    }
+}
+
+// Software check for frames the controller passed without checking, so that frames handed out
+// with receive checksum offload enabled can be trusted. Covers the IPv4 header and TCP and UDP
+// over either IP version, but not fragments, whose datagrams the stack checks once reassembled.
+pub(crate) fn frame_is_valid<'b>(segments: impl Iterator<Item = &'b [u8]> + Clone) -> bool {
+    let frame = Frame::new(segments);
+    let mut offset = 12;
+    let mut ethertype = frame.be16(offset);
+    while let Some(ETHERTYPE_VLAN | ETHERTYPE_QINQ) = ethertype {
+        offset += 4;
+        ethertype = frame.be16(offset);
+    }
+    match ethertype {
+        Some(ETHERTYPE_IPV4) => ipv4_is_valid(&frame, offset + 2),
+        Some(ETHERTYPE_IPV6) => ipv6_is_valid(&frame, offset + 2),
+        Some(_) => true,
+        None => false,
+    }
+}
+
+fn ipv4_is_valid<'b, S: Iterator<Item = &'b [u8]> + Clone>(frame: &Frame<S>, ip: usize) -> bool {
+    let mut header = [0u8; 60];
+    if !frame.read(ip, &mut header[..20]) {
+        return false;
+    }
+    let header_len = ((header[0] & 0x0F) as usize) * 4;
+    let total_len = u16::from_be_bytes([header[2], header[3]]) as usize;
+    if header_len < 20
+        || total_len < header_len
+        || ip + total_len > frame.len
+        || !frame.read(ip, &mut header[..header_len])
+    {
+        return false;
+    }
+    if fold(sum(&header[..header_len], 0)) != 0xFFFF {
+        return false;
+    }
+
+    // Fragments can only be checked once reassembled
+    if u16::from_be_bytes([header[6], header[7]]) & 0x3FFF != 0 {
+        return true;
+    }
+
+    let payload_len = total_len - header_len;
+    let pseudo = sum(&header[12..20], 0) + header[9] as u32 + payload_len as u32;
+    transport_is_valid(frame, header[9], ip + header_len, payload_len, pseudo, true)
+}
+
+fn ipv6_is_valid<'b, S: Iterator<Item = &'b [u8]> + Clone>(frame: &Frame<S>, ip: usize) -> bool {
+    let mut header = [0u8; 40];
+    if !frame.read(ip, &mut header) {
+        return false;
+    }
+    let end = ip + 40 + u16::from_be_bytes([header[4], header[5]]) as usize;
+    if end > frame.len {
+        return false;
+    }
+
+    // Skip the extension headers in front of the transport header
+    let (mut next, mut offset) = (header[6], ip + 40);
+    loop {
+        let mut ext = [0u8; 2];
+        match next {
+            IPV6_HOP_BY_HOP | IPV6_ROUTING | IPV6_DEST_OPTIONS => {
+                if offset + 2 > end || !frame.read(offset, &mut ext) {
+                    return false;
+                }
+                next = ext[0];
+                offset += (ext[1] as usize + 1) * 8;
+            }
+            // Fragments can only be checked once reassembled
+            IPV6_FRAGMENT => return true,
+            _ => break,
+        }
+    }
+    if offset > end {
+        return false;
+    }
+
+    let payload_len = end - offset;
+    let pseudo = sum(&header[8..40], 0) + next as u32 + payload_len as u32;
+    transport_is_valid(frame, next, offset, payload_len, pseudo, false)
+}
+
+// Checks the TCP or UDP checksum of the `len` byte payload at `offset`, given the sum of the
+// pseudo header. IPv6 has no UDP datagrams without a checksum.
+fn transport_is_valid<'b, S: Iterator<Item = &'b [u8]> + Clone>(
+    frame: &Frame<S>,
+    protocol: u8,
+    offset: usize,
+    len: usize,
+    pseudo: u32,
+    udp_zero_allowed: bool,
+) -> bool {
+    match protocol {
+        IP_PROTO_TCP if len >= 20 => fold(frame.sum(offset, len, pseudo)) == 0xFFFF,
+        IP_PROTO_UDP if len >= 8 => match frame.be16(offset + 6) {
+            Some(0) => udp_zero_allowed,
+            _ => fold(frame.sum(offset, len, pseudo)) == 0xFFFF,
+        },
+        IP_PROTO_TCP | IP_PROTO_UDP => false,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern crate std;
+    use std::vec::Vec;
+
+    // An Ethernet frame carrying `payload` over IPv4 or IPv6, with every checksum filled in
+    fn frame(ipv6: bool, protocol: u8, mut payload: Vec<u8>) -> Vec<u8> {
+        let mut frame = Vec::from([0xFF; 12]);
+        let pseudo = if ipv6 {
+            frame.extend_from_slice(&ETHERTYPE_IPV6.to_be_bytes());
+            frame.extend_from_slice(&[0x60, 0, 0, 0]);
+            frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+            frame.extend_from_slice(&[protocol, 64]);
+            frame.extend((0..32).map(|i| i as u8));
+            sum(&frame[22..54], 0)
+        } else {
+            frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+            frame.extend_from_slice(&[0x45, 0]);
+            frame.extend_from_slice(&(20 + payload.len() as u16).to_be_bytes());
+            frame.extend_from_slice(&[0, 0, 0, 0, 64, protocol, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2]);
+            let check = !fold(sum(&frame[14..34], 0));
+            frame[24..26].copy_from_slice(&check.to_be_bytes());
+            sum(&frame[26..34], 0)
+        };
+        let at = if protocol == IP_PROTO_TCP { 16 } else { 6 };
+        payload[at..at + 2].fill(0);
+        let check = !fold(sum(
+            &payload,
+            pseudo + protocol as u32 + payload.len() as u32,
+        ));
+        payload[at..at + 2].copy_from_slice(&check.to_be_bytes());
+        frame.extend(payload);
+        frame
+    }
+
+    fn valid(frame: &[u8]) -> bool {
+        frame_is_valid(core::iter::once(frame))
+    }
+
+    #[test]
+    fn transport_checksums_verified() {
+        for ipv6 in [false, true] {
+            for protocol in [IP_PROTO_TCP, IP_PROTO_UDP] {
+                let good = frame(ipv6, protocol, (0..101).map(|i| i as u8).collect());
+                assert!(valid(&good));
+
+                // Also over buffers split at odd offsets
+                let parts = [&good[..15], &good[15..57], &good[57..]];
+                assert!(frame_is_valid(parts.iter().copied()));
+
+                let mut bad = good.clone();
+                *bad.last_mut().unwrap() ^= 1;
+                assert!(!valid(&bad));
+                let parts = [&bad[..15], &bad[15..57], &bad[57..]];
+                assert!(!frame_is_valid(parts.iter().copied()));
+                assert!(!valid(&good[..good.len() - 1]));
+            }
+        }
+
+        // A broken IPv4 header fails on its own
+        let mut bad = frame(false, IP_PROTO_UDP, Vec::from([0; 8]));
+        bad[22] ^= 1;
+        assert!(!valid(&bad));
+    }
+
+    #[test]
+    fn zero_udp_checksum() {
+        // Means no checksum over IPv4, and is not allowed over IPv6
+        for ipv6 in [false, true] {
+            let mut zero = frame(ipv6, IP_PROTO_UDP, Vec::from([0x5A; 16]));
+            let at = zero.len() - 16 + 6;
+            zero[at..at + 2].fill(0);
+            assert_eq!(valid(&zero), !ipv6);
+        }
+
+        // Anything other than IP is left alone
+        let mut arp = Vec::from([0xFF; 60]);
+        arp[12..14].copy_from_slice(&[0x08, 0x06]);
+        assert!(valid(&arp));
+        assert!(!valid(&arp[..13]));
+    }
+}
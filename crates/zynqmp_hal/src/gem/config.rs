@@ -0,0 +1,332 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use super::capabilities::GemCapabilities;
+use super::interrupts::{GemInterrupts, InterruptCoalescing};
+use super::ring::RX_BUF_UNIT;
+
+// AXI burst length used by the DMA
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BurstLength {
+    Single = 1,
+    Incr4 = 4,
+    Incr8 = 8,
+    Incr16 = 16,
+}
+
+// Controller configuration applied by `Device<Reset>::init`. Starts from the settings the HAL
+// has always used, override what a product needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GemConfig {
+    pub(crate) rx_buf_size: usize,
+    pub(crate) rx_pbuf_size: Option<usize>,
+    pub(crate) tx_pbuf_size: Option<usize>,
+    pub(crate) burst_length: BurstLength,
+    pub(crate) force_max_burst: bool,
+    // Outstanding AXI reads and writes, None leaves the reset value
+    pub(crate) axi_pipeline: Option<(u8, u8)>,
+    // Cut-through thresholds in bytes
+    pub(crate) tx_cut_through: Option<usize>,
+    pub(crate) rx_cut_through: Option<usize>,
+    pub(crate) fcs_remove: bool,
+    pub(crate) no_broadcast: bool,
+    pub(crate) promiscuous: bool,
+    pub(crate) rx_buf_offset: usize,
+    pub(crate) checksum_offload: Option<bool>,
+    pub(crate) interrupts: GemInterrupts,
+    pub(crate) coalescing: InterruptCoalescing,
+    pub(crate) endian_swap_packet: bool,
+    pub(crate) endian_swap_management: bool,
+}
+
+impl GemConfig {
+    pub const fn new() -> Self {
+        Self {
+            rx_buf_size: 1600,
+            rx_pbuf_size: None,
+            tx_pbuf_size: None,
+            burst_length: BurstLength::Incr4,
+            force_max_burst: false,
+            axi_pipeline: None,
+            tx_cut_through: None,
+            rx_cut_through: None,
+            fcs_remove: false,
+            no_broadcast: false,
+            promiscuous: false,
+            rx_buf_offset: 0,
+            checksum_offload: None,
+            interrupts: GemInterrupts::RX_COMPLETE.union(GemInterrupts::TX_COMPLETE),
+            coalescing: InterruptCoalescing::new(0, 0),
+            endian_swap_packet: false,
+            endian_swap_management: false,
+        }
+    }
+
+    // Size of each RX DMA buffer, a multiple of 64 bytes. The RX ring handed to `Driver::new`
+    // has to have buffers of exactly this size.
+    pub const fn rx_buf_size(mut self, size: usize) -> Self {
+        self.rx_buf_size = size;
+        self
+    }
+
+    // RX packet buffer size in bytes, 4, 8, 16 or 32 KiB. Defaults to the largest the instance
+    // has.
+    pub const fn rx_pbuf_size(mut self, size: usize) -> Self {
+        self.rx_pbuf_size = Some(size);
+        self
+    }
+
+    // TX packet buffer size in bytes, 2 or 4 KiB. Defaults to the largest the instance has.
+    pub const fn tx_pbuf_size(mut self, size: usize) -> Self {
+        self.tx_pbuf_size = Some(size);
+        self
+    }
+
+    pub const fn burst_length(mut self, burst: BurstLength) -> Self {
+        self.burst_length = burst;
+        self
+    }
+
+    // Always use bursts of the full `burst_length`, rather than shorter ones where they fit
+    // better
+    pub const fn force_max_burst(mut self, enable: bool) -> Self {
+        self.force_max_burst = enable;
+        self
+    }
+
+    // Number of AXI read and write transactions the DMA may have outstanding, at least 1
+    pub const fn axi_max_pipeline(mut self, reads: u8, writes: u8) -> Self {
+        self.axi_pipeline = Some((reads, writes));
+        self
+    }
+
+    // Start sending a frame once `threshold` bytes of it are in the TX packet buffer, instead
+    // of waiting for all of it. Rules out TX checksum offload, which needs the whole frame.
+    pub const fn tx_cut_through(mut self, threshold: usize) -> Self {
+        self.tx_cut_through = Some(threshold);
+        self
+    }
+
+    // Start writing a frame to memory once `threshold` bytes of it are in the RX packet buffer
+    pub const fn rx_cut_through(mut self, threshold: usize) -> Self {
+        self.rx_cut_through = Some(threshold);
+        self
+    }
+
+    // Strip the FCS from received frames before they are written to memory
+    pub const fn fcs_remove(mut self, enable: bool) -> Self {
+        self.fcs_remove = enable;
+        self
+    }
+
+    pub const fn reject_broadcast(mut self, enable: bool) -> Self {
+        self.no_broadcast = enable;
+        self
+    }
+
+    pub const fn promiscuous(mut self, enable: bool) -> Self {
+        self.promiscuous = enable;
+        self
+    }
+
+    // Bytes, up to 3, left free at the start of each RX buffer. Two aligns the IP header.
+    pub const fn rx_buf_offset(mut self, offset: usize) -> Self {
+        self.rx_buf_offset = offset;
+        self
+    }
+
+    // RX checksum checking and TX checksum generation, only available with a packet buffer.
+    // Defaults to on when the instance has one.
+    pub const fn checksum_offload(mut self, enable: bool) -> Self {
+        self.checksum_offload = Some(enable);
+        self
+    }
+
+    // Interrupt sources enabled once initialised
+    pub const fn interrupts(mut self, interrupts: GemInterrupts) -> Self {
+        self.interrupts = interrupts;
+        self
+    }
+
+    // Delay before RX and TX complete interrupts, see `InterruptCoalescing`. Off by default.
+    pub const fn interrupt_coalescing(mut self, coalescing: InterruptCoalescing) -> Self {
+        self.coalescing = coalescing;
+        self
+    }
+
+    // Swap the endianness of frame data and of descriptors respectively
+    pub const fn endian_swap(mut self, packet: bool, management: bool) -> Self {
+        self.endian_swap_packet = packet;
+        self.endian_swap_management = management;
+        self
+    }
+
+    pub(crate) fn checksum_offload_enabled(&self, caps: &GemCapabilities) -> bool {
+        self.checksum_offload
+            .unwrap_or(caps.has_packet_buffer() && self.tx_cut_through.is_none())
+    }
+
+    // Cut-through threshold in packet buffer locations, one per data bus word, checked
+    // against the packet buffer it applies to
+    fn cut_through_config(
+        threshold: usize,
+        pbuf_size: usize,
+        caps: &GemCapabilities,
+    ) -> Result<u32, &'static str> {
+        if !caps.cut_through || !caps.has_packet_buffer() {
+            return Err("GEM has no cut-through support");
+        }
+        let word = caps.dma_bus_width.bits() / 8;
+        let locations = threshold.div_ceil(word);
+        if locations == 0 {
+            return Err("Invalid cut-through threshold");
+        }
+        if locations >= pbuf_size / word || locations > 0xFFF {
+            return Err("Cut-through threshold larger than the packet buffer");
+        }
+        Ok(locations as u32)
+    }
+
+    // pbuf_txcutthru DMA_TX_CUTTHRU_THRESHOLD, if TX cut-through is wanted
+    pub(crate) fn tx_cut_through_config(
+        &self,
+        caps: &GemCapabilities,
+    ) -> Result<Option<u32>, &'static str> {
+        match self.tx_cut_through {
+            None => Ok(None),
+            Some(threshold) => {
+                let pbuf_size = 2048 << self.tx_pbuf_config(caps)?;
+                Self::cut_through_config(threshold, pbuf_size, caps).map(Some)
+            }
+        }
+    }
+
+    // pbuf_rxcutthru DMA_RX_CUTTHRU_THRESHOLD, if RX cut-through is wanted
+    pub(crate) fn rx_cut_through_config(
+        &self,
+        caps: &GemCapabilities,
+    ) -> Result<Option<u32>, &'static str> {
+        match self.rx_cut_through {
+            None => Ok(None),
+            Some(threshold) => {
+                let pbuf_size = 4096 << self.rx_pbuf_config(caps)?;
+                Self::cut_through_config(threshold, pbuf_size, caps).map(Some)
+            }
+        }
+    }
+
+    // dma_config RX_PBUF_SIZE encoding, checked against what the instance has
+    pub(crate) fn rx_pbuf_config(&self, caps: &GemCapabilities) -> Result<u32, &'static str> {
+        let size = match self.rx_pbuf_size {
+            None => return Ok(caps.rx_pbuf_config()),
+            Some(size) => size,
+        };
+        if caps.rx_pbuf_size == 0 {
+            return Err("GEM has no RX packet buffer");
+        }
+        if size > caps.rx_pbuf_size {
+            return Err("RX packet buffer larger than the GEM has");
+        }
+        match size {
+            4096 => Ok(0),
+            8192 => Ok(1),
+            16384 => Ok(2),
+            32768 => Ok(3),
+            _ => Err("Invalid RX packet buffer size"),
+        }
+    }
+
+    // dma_config TX_PBUF_SIZE encoding, checked against what the instance has
+    pub(crate) fn tx_pbuf_config(&self, caps: &GemCapabilities) -> Result<u32, &'static str> {
+        let size = match self.tx_pbuf_size {
+            None => return Ok(caps.tx_pbuf_config()),
+            Some(size) => size,
+        };
+        if caps.tx_pbuf_size == 0 {
+            return Err("GEM has no TX packet buffer");
+        }
+        if size > caps.tx_pbuf_size {
+            return Err("TX packet buffer larger than the GEM has");
+        }
+        match size {
+            2048 => Ok(0),
+            4096 => Ok(1),
+            _ => Err("Invalid TX packet buffer size"),
+        }
+    }
+
+    pub(crate) fn validate(&self, caps: &GemCapabilities) -> Result<(), &'static str> {
+        if self.rx_buf_size == 0
+            || !self.rx_buf_size.is_multiple_of(RX_BUF_UNIT)
+            || self.rx_buf_size > 0xFF * RX_BUF_UNIT
+        {
+            return Err("Invalid RX buffer size");
+        }
+        if self.rx_buf_offset > 3 {
+            return Err("Invalid RX buffer offset");
+        }
+        if self.checksum_offload == Some(true) && !caps.has_packet_buffer() {
+            return Err("Checksum offload needs a packet buffer");
+        }
+        self.rx_pbuf_config(caps)?;
+        self.tx_pbuf_config(caps)?;
+        if self.tx_cut_through_config(caps)?.is_some() && self.checksum_offload == Some(true) {
+            return Err("TX checksum offload needs store and forward");
+        }
+        self.rx_cut_through_config(caps)?;
+        if let Some((0, _) | (_, 0)) = self.axi_pipeline {
+            return Err("Invalid AXI pipeline depth");
+        }
+        Ok(())
+    }
+}
+
+impl Default for GemConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gem::sim::GemSim;
+    use crate::gem::Device;
+
+    #[test]
+    fn cut_through_validated() {
+        let mut sim = GemSim::new();
+        let ptr = sim.ptr();
+        let dev = || unsafe { Device::new(ptr) };
+
+        // 64-bit bus, so thresholds are in 8 byte locations of a 4 KiB TX packet buffer
+        let config = GemConfig::new()
+            .tx_cut_through(500)
+            .rx_cut_through(256)
+            .axi_max_pipeline(8, 4)
+            .force_max_burst(true);
+        let _ = dev().init(config).unwrap();
+        assert_eq!(sim.read_reg(0x40), 0x8000_003F);
+        assert_eq!(sim.read_reg(0x44), 0x8000_0020);
+        assert_eq!(sim.read_reg(0x54), 0x0000_0408);
+        // No TX checksum generation unless asked for, and then it is refused
+        assert_eq!(sim.read_reg(0x10) & (1 << 11), 0);
+        assert!(dev()
+            .init(config.checksum_offload(true))
+            .is_err_and(|e| e.contains("store and forward")));
+
+        assert!(dev().init(GemConfig::new().tx_cut_through(0)).is_err());
+        assert!(dev().init(GemConfig::new().tx_cut_through(4096)).is_err());
+        assert!(dev()
+            .init(GemConfig::new().tx_pbuf_size(2048).tx_cut_through(4000))
+            .is_err());
+        assert!(dev().init(GemConfig::new().axi_max_pipeline(0, 4)).is_err());
+
+        // Instances built without cut-through refuse it
+        sim.write_reg(0x294, 0);
+        assert!(dev().init(GemConfig::new().rx_cut_through(256)).is_err());
+    }
+}
@@ -0,0 +1,1354 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use core::ops::{Deref, DerefMut};
+
+use tock_registers::interfaces::Readable;
+
+use eth_phy::LinkStatus;
+use zynqmp_pac::gem::*;
+
+use super::checksum::{frame_is_valid, ChecksumOffload, RxChecksum};
+use super::ring::{RxRing, TxCompletion, TxFrameId, TxRing, RX_BUF_UNIT};
+use super::status::{RecvStatus, RxFrameInfo, TxStatus};
+use super::suspend::{SavedState, WakeOnLan};
+use super::vlan::VlanTag;
+use super::{AnyGem, Config, Device, MacAddress, Running, Suspended};
+
+pub const FCS_LEN: usize = 4;
+const MAX_FRAME_LEN: usize = 1518;
+const MAX_FRAME_LEN_1536: usize = 1536;
+// Buffers one scatter-gather frame can be built from
+pub const MAX_SG_PARTS: usize = 16;
+
+// Error events the driver recovered from
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DriverStats {
+    // Frames lost to the RX FIFO overflowing
+    pub rx_overruns: u64,
+    // Times the controller found no free RX descriptor
+    pub rx_buffers_unavailable: u64,
+    // Frames dropped from the packet buffer after waiting for a descriptor
+    pub rx_flushed: u64,
+    // HRESP errors, each followed by a restart of reception
+    pub rx_bus_errors: u64,
+    // TX completions dropped because more than `TX_COMPLETION_QUEUE` were waiting for
+    // `reclaim_tx_with`
+    pub tx_completions_dropped: u64,
+}
+
+// A running controller together with the descriptor rings it is working on
+pub struct Driver<'a, I = AnyGem> {
+    dev: Device<Running, I>,
+    rx: RxRing<'a>,
+    tx: TxRing<'a>,
+    stats: DriverStats,
+    // No RX descriptor was available to the controller although some were free
+    rx_starved: bool,
+}
+
+impl<'a, I> Driver<'a, I> {
+    // The RX ring buffers have to be of the size the device was set up for with
+    // `GemConfig::rx_buf_size`
+    pub fn new(
+        dev: Device<Config, I>,
+        mut rx: RxRing<'a>,
+        mut tx: TxRing<'a>,
+    ) -> Result<Self, &'static str> {
+        Self::program_rings(&dev, &mut rx, &mut tx)?;
+        Ok(Self {
+            dev: dev.run(),
+            rx,
+            tx,
+            stats: DriverStats::default(),
+            rx_starved: false,
+        })
+    }
+
+    fn program_rings(
+        dev: &Device<Config, I>,
+        rx: &mut RxRing<'a>,
+        tx: &mut TxRing<'a>,
+    ) -> Result<(), &'static str> {
+        if dev.dma_config.read(dma_config::RX_BUF_SIZE) as usize * RX_BUF_UNIT != rx.buf_size() {
+            return Err("RX buffer size differs from the configured one");
+        }
+        // There is one OR mask for both directions
+        let (value, enable) = tx.translation().or_mask();
+        if rx.translation().or_mask() != (value, enable) {
+            return Err("RX and TX rings need the same OR mask");
+        }
+
+        rx.set_offset(
+            dev.network_config
+                .read(network_config::RECEIVE_BUFFER_OFFSET) as usize,
+        );
+        // The controller starts from the ring bases, whatever the rings were used for before
+        rx.reset();
+        tx.reset();
+        dev.set_dma_or_mask(value, enable);
+        dev.set_rx_desc(rx.dma_base_addr());
+        dev.set_tx_desc(tx.dma_base_addr());
+        Ok(())
+    }
+
+    // Quiesce the controller and reprogram it for a newly negotiated link. Disabling transmit
+    // rewinds the queue pointers, so both rings start over and frames still in flight are
+    // aborted, reported through `reclaim_tx_with`.
+    pub fn apply_link(&mut self, link: &LinkStatus) {
+        let (rx, tx) = (&mut self.rx, &mut self.tx);
+        self.dev.reconfigure(|dev| {
+            dev.set_speed(link.speed);
+            dev.set_duplex(link.duplex);
+            dev.set_pause(link.pause);
+
+            rx.reset();
+            tx.reclaim();
+            tx.abort();
+            tx.reset();
+            dev.set_rx_desc(rx.dma_base_addr());
+            dev.set_tx_desc(tx.dma_base_addr());
+        });
+        self.rx_starved = false;
+    }
+
+    // Send what is queued and suspend the controller, see `Device::suspend`. Frames received
+    // but not taken yet are dropped on resume. The driver is handed back still running if
+    // transmission does not finish.
+    #[allow(clippy::result_large_err)]
+    pub fn suspend(mut self, wol: Option<WakeOnLan>) -> Result<SuspendedDriver<'a, I>, Self> {
+        let (dev, saved) = match self.dev.suspend(wol) {
+            Ok(suspended) => suspended,
+            Err(dev) => return Err(Self { dev, ..self }),
+        };
+        self.tx.reclaim();
+        Ok(SuspendedDriver {
+            dev,
+            saved,
+            rx: self.rx,
+            tx: self.tx,
+            stats: self.stats,
+        })
+    }
+
+    #[must_use]
+    pub fn release(self) -> (Device<Config, I>, RxRing<'a>, TxRing<'a>) {
+        let dev = self.dev.stop();
+        (dev, self.rx, self.tx)
+    }
+
+    // As `release`, once the frames already queued have gone out. The driver is handed back
+    // still running if they do not, `release` it instead.
+    #[allow(clippy::result_large_err)]
+    pub fn shutdown(mut self) -> Result<(Device<Config, I>, RxRing<'a>, TxRing<'a>), Self> {
+        let dev = match self.dev.shutdown() {
+            Ok(dev) => dev,
+            Err(dev) => return Err(Self { dev, ..self }),
+        };
+        self.tx.reclaim();
+        Ok((dev, self.rx, self.tx))
+    }
+
+    pub fn device(&self) -> &Device<Running, I> {
+        &self.dev
+    }
+
+    pub fn mac_address(&self) -> MacAddress {
+        self.dev.mac_address()
+    }
+
+    // Largest frame, without FCS, that both rings and the MAC configuration can carry
+    pub fn mtu(&self) -> usize {
+        let cfg = self.dev.network_config.extract();
+        let max_frame = if cfg.is_set(network_config::JUMBO_FRAMES) {
+            self.dev
+                .jumbo_max_length
+                .read(jumbo_max_length::JUMBO_MAX_LENGTH) as usize
+        } else if cfg.is_set(network_config::RECEIVE_1536_BYTE_FRAMES) {
+            MAX_FRAME_LEN_1536
+        } else {
+            MAX_FRAME_LEN
+        };
+
+        // The FCS only takes up buffer space when it is not removed on receive
+        let rx_space = self.rx.buf_size()
+            - cfg.read(network_config::RECEIVE_BUFFER_OFFSET) as usize
+            - if cfg.is_set(network_config::FCS_REMOVE) {
+                0
+            } else {
+                FCS_LEN
+            };
+
+        (max_frame - FCS_LEN).min(rx_space).min(self.tx.buf_size())
+    }
+
+    pub fn checksum_offload(&self) -> ChecksumOffload {
+        ChecksumOffload {
+            rx: self
+                .dev
+                .network_config
+                .is_set(network_config::RECEIVE_CHECKSUM_OFFLOAD_ENABLE),
+            tx: self.dev.dma_config.is_set(dma_config::TX_PBUF_TCP_EN),
+        }
+    }
+
+    pub fn tx_ring_len(&self) -> usize {
+        self.tx.len()
+    }
+
+    pub fn stats(&self) -> DriverStats {
+        DriverStats {
+            tx_completions_dropped: self.tx.completions_dropped(),
+            ..self.stats
+        }
+    }
+
+    // Lend the next received frame straight out of its DMA buffer. The buffer goes back to
+    // the controller when the frame is dropped. Frames spread over several buffers are dropped
+    // here, use `receive_chain` when RX buffers are smaller than the largest frame.
+    pub fn receive(&mut self) -> Option<RxFrame<'_, 'a>> {
+        let rx_offload = self.checksum_offload().rx;
+        if !self.rx_pending() {
+            return None;
+        }
+        Self::next_frame(&mut self.rx, rx_offload)
+    }
+
+    // Hand up to `budget` received frames to `f`, returning how many there were. For NAPI style
+    // processing under load: leave RX interrupts disabled and keep polling while the whole
+    // budget is used, and enable them again once a poll comes up short.
+    pub fn poll<F>(&mut self, budget: usize, mut f: F) -> usize
+    where
+        F: FnMut(RxFrame<'_, 'a>),
+    {
+        let mut count = 0;
+        while count < budget {
+            match self.receive() {
+                Some(frame) => f(frame),
+                None => break,
+            }
+            count += 1;
+        }
+        count
+    }
+
+    // Lend the next received frame as the chain of DMA buffers it was written to
+    pub fn receive_chain(&mut self) -> Option<RxChain<'_, 'a>> {
+        let rx_offload = self.checksum_offload().rx;
+        if !self.rx_pending() {
+            return None;
+        }
+        let (descriptors, info) = loop {
+            let (descriptors, len) = self.rx.pending_chain()?;
+            let info = RxFrameInfo::from_chain(
+                self.rx.status(),
+                self.rx.status_at(descriptors - 1),
+                rx_offload,
+                self.rx.segment(0, len),
+            );
+
+            // As in `next_frame`
+            let rx = &self.rx;
+            if !rx_offload
+                || info.checksum != RxChecksum::NotChecked
+                || frame_is_valid((0..descriptors).map(|i| rx.segment(i, len)))
+            {
+                break (descriptors, info);
+            }
+            self.rx.release_n(descriptors);
+        };
+        Some(RxChain {
+            rx: &mut self.rx,
+            descriptors,
+            info,
+        })
+    }
+
+    // Copy the next received frame into `buf`, returning its length. A frame which does not
+    // fit is dropped.
+    pub fn receive_into(&mut self, buf: &mut [u8]) -> Option<Result<usize, &'static str>> {
+        Some(self.receive_chain()?.copy_to(buf))
+    }
+
+    // Write a frame of `len` bytes straight into a free TX buffer and queue it. None when the
+    // ring is full or the frame does not fit a TX buffer, see `mtu`.
+    pub fn transmit<R, F>(&mut self, len: usize, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        self.transmit_token()?.consume(len, f).ok()
+    }
+
+    // Queue a frame from a caller provided buffer without copying it. The buffer is returned
+    // by `complete` once the controller is done with it, or handed back straight away when
+    // there is no free descriptor or the DMA cannot reach it.
+    pub fn transmit_buffer(
+        &mut self,
+        buf: &'static mut [u8],
+    ) -> Result<TxInFlight, &'static mut [u8]> {
+        let addr = buf.as_ptr() as usize;
+        if !self.tx.can_reach(addr, buf.len()) || !self.tx_room(1) {
+            return Err(buf);
+        }
+        let seq = self.tx.submit_external(addr, buf.len());
+        self.dev.start_tx();
+        Ok(TxInFlight {
+            buf,
+            ring: self.tx.base_addr(),
+            seq,
+        })
+    }
+
+    // Take back the buffers of a frame queued with `transmit_buffer` or `transmit_sg`, if it
+    // has been sent. Its completion waits for `reclaim_tx_with`.
+    pub fn complete<B>(&mut self, frame: TxInFlight<B>) -> Result<B, TxInFlight<B>> {
+        self.tx.reclaim();
+        if frame.ring != self.tx.base_addr() || !self.tx.is_retired(frame.seq) {
+            return Err(frame);
+        }
+        Ok(frame.buf)
+    }
+
+    // Queue one frame gathered from several buffers, e.g. headers and payload kept apart,
+    // without copying them. As with `transmit_buffer` the buffers are held until `complete`
+    // gives them back, or handed back straight away when they cannot be queued. Empty buffers
+    // are skipped.
+    pub fn transmit_sg<const N: usize>(
+        &mut self,
+        parts: [&'static mut [u8]; N],
+    ) -> Result<TxInFlight<[&'static mut [u8]; N]>, [&'static mut [u8]; N]> {
+        let mut gathered: [&[u8]; MAX_SG_PARTS] = [&[]; MAX_SG_PARTS];
+        let mut count = 0;
+        for part in parts.iter().filter(|part| !part.is_empty()) {
+            // Too many buffers for one frame, or out of DMA reach
+            if count == MAX_SG_PARTS
+                || count == self.tx.len()
+                || !self.tx.can_reach(part.as_ptr() as usize, part.len())
+            {
+                return Err(parts);
+            }
+            gathered[count] = part;
+            count += 1;
+        }
+        if count == 0 || !self.tx_room(count) {
+            return Err(parts);
+        }
+
+        let seq = self.tx.submit_gather(&gathered[..count]);
+        self.dev.start_tx();
+        Ok(TxInFlight {
+            buf: parts,
+            ring: self.tx.base_addr(),
+            seq,
+        })
+    }
+
+    // Token pair for network stacks, only handed out when a reply could be sent
+    pub fn receive_tokens(&mut self) -> Option<(RxToken<'_, 'a>, TxToken<'_, 'a, I>)> {
+        let rx_offload = self.checksum_offload().rx;
+        if !self.tx_room(1) || !self.rx_pending() {
+            return None;
+        }
+        let frame = Self::next_frame(&mut self.rx, rx_offload)?;
+        Some((
+            RxToken { frame },
+            TxToken {
+                dev: &self.dev,
+                tx: &mut self.tx,
+            },
+        ))
+    }
+
+    pub fn transmit_token(&mut self) -> Option<TxToken<'_, 'a, I>> {
+        if !self.tx_room(1) {
+            return None;
+        }
+        Some(TxToken {
+            dev: &self.dev,
+            tx: &mut self.tx,
+        })
+    }
+
+    // Retire transmitted frames, discarding their completions along with those still queued.
+    // Returns the number of completions discarded.
+    pub fn reclaim_tx(&mut self) -> usize {
+        self.tx.reclaim_with(|_| ())
+    }
+
+    // As `reclaim_tx`, reporting the outcome of each frame in submission order. Frames retired
+    // on the way, e.g. to make room or by `complete`, are reported here first, up to
+    // `TX_COMPLETION_QUEUE` of them.
+    pub fn reclaim_tx_with<F: FnMut(TxCompletion)>(&mut self, f: F) -> usize {
+        self.tx.reclaim_with(f)
+    }
+
+    // Retire transmitted frames, recovering first if transmission stopped on an error. Returns
+    // the transmit status, which is cleared, or the error of `recover_tx`.
+    pub fn poll_tx<F: FnMut(TxCompletion)>(&mut self, f: F) -> Result<TxStatus, &'static str> {
+        let status = self.dev.get_transmit_status();
+        if status.intersects(TxStatus::ERRORS) {
+            self.recover_tx(f)?;
+        } else {
+            self.tx.reclaim_with(f);
+        }
+        Ok(status)
+    }
+
+    // Bring transmission back after an error. The controller is halted, frames it finished are
+    // retired as usual, everything else still queued is failed with `TxError::Aborted`, and it
+    // restarts on the emptied ring. Returns the number of frames retired. Fails, leaving the
+    // ring alone, if the controller does not halt within `TX_IDLE_POLLS`.
+    pub fn recover_tx<F: FnMut(TxCompletion)>(&mut self, mut f: F) -> Result<usize, &'static str> {
+        self.dev.halt_tx()?;
+        let count = self.tx.reclaim_with(&mut f) + self.tx.abort_with(&mut f);
+        self.restart_tx();
+        Ok(count)
+    }
+
+    // As `poll_tx`, keeping the completions for `reclaim_tx_with`
+    pub(crate) fn poll_tx_queued(&mut self) -> Result<(), &'static str> {
+        if self.dev.get_transmit_status().intersects(TxStatus::ERRORS) {
+            self.dev.halt_tx()?;
+            self.tx.reclaim();
+            self.tx.abort();
+            self.restart_tx();
+        } else {
+            self.tx.reclaim();
+        }
+        Ok(())
+    }
+
+    fn restart_tx(&mut self) {
+        self.tx.reset();
+        self.dev.get_transmit_status();
+        self.dev.restart_tx(self.tx.dma_base_addr());
+    }
+
+    // Whether `count` descriptors are free. A full ring may be down to the transmitter having
+    // stopped on an error, so check for that before giving up.
+    fn tx_room(&mut self, count: usize) -> bool {
+        if self.tx.has_room(count) {
+            return true;
+        }
+        self.poll_tx_queued().is_ok() && self.tx.has_room(count)
+    }
+
+    // Check the receive status and recover from what it reports. Descriptors left holding parts
+    // of abandoned frames are handed back, a frame which keeps waiting for a descriptor although
+    // some are free is flushed, and after a bus error reception restarts on an emptied ring.
+    // Returns the receive status, which is cleared.
+    pub fn poll_rx(&mut self) -> RecvStatus {
+        let status = self.dev.get_receive_status();
+        if status.contains(RecvStatus::FIFO_OVERFLOW) {
+            self.stats.rx_overruns += 1;
+        }
+        if status.contains(RecvStatus::RESP_NOT_OK) {
+            // The DMA stops on an HRESP error, frames not yet taken are dropped
+            self.stats.rx_bus_errors += 1;
+            self.rx.reset();
+            self.rx_starved = false;
+            self.dev.restart_rx(self.rx.dma_base_addr());
+        } else if status.contains(RecvStatus::UNAVAILABLE_BUFFER) {
+            // Reported once with descriptors free the frame may just have come in before they
+            // were handed back, so only a second report means it is stuck
+            self.stats.rx_buffers_unavailable += 1;
+            let free = self.rx.refill();
+            if free && self.rx_starved {
+                self.dev.flush_rx_packet();
+                self.stats.rx_flushed += 1;
+                self.rx_starved = false;
+            } else {
+                self.rx_starved = free;
+            }
+        } else {
+            self.rx_starved = false;
+        }
+        status
+    }
+
+    // Whether a frame is waiting. An empty ring may be down to reception having stopped on an
+    // error, so check for that before giving up.
+    fn rx_pending(&mut self) -> bool {
+        if self.rx.has_pending() {
+            return true;
+        }
+        self.poll_rx();
+        self.rx.has_pending()
+    }
+
+    fn next_frame<'d>(rx: &'d mut RxRing<'a>, rx_offload: bool) -> Option<RxFrame<'d, 'a>> {
+        let info = loop {
+            let len = rx.pending()?;
+            let info = RxFrameInfo::new(rx.status(), rx_offload, rx.buffer(len));
+
+            // The controller drops frames it finds bad checksums in, but passes frames it could
+            // not check. Check them here so every frame handed out can be trusted.
+            if !rx_offload
+                || info.checksum != RxChecksum::NotChecked
+                || frame_is_valid(core::iter::once(rx.frame(len)))
+            {
+                break info;
+            }
+            rx.release();
+        };
+        Some(RxFrame { rx, info })
+    }
+}
+
+// A driver whose controller is suspended, holding on to its rings until it is resumed
+pub struct SuspendedDriver<'a, I = AnyGem> {
+    dev: Device<Suspended, I>,
+    saved: SavedState,
+    rx: RxRing<'a>,
+    tx: TxRing<'a>,
+    stats: DriverStats,
+}
+
+impl<'a, I> SuspendedDriver<'a, I> {
+    pub fn device(&self) -> &Device<Suspended, I> {
+        &self.dev
+    }
+
+    // Reprogram the controller and start both rings over from their base
+    #[must_use]
+    pub fn resume(mut self) -> Driver<'a, I> {
+        self.rx.reset();
+        self.tx.reset();
+        let saved = self
+            .saved
+            .with_queues(self.rx.dma_base_addr(), self.tx.dma_base_addr());
+        Driver {
+            dev: self.dev.resume(&saved),
+            rx: self.rx,
+            tx: self.tx,
+            stats: self.stats,
+            rx_starved: false,
+        }
+    }
+}
+
+// A received frame still in its DMA buffer. Software owns the descriptor for as long as the
+// frame is alive, dropping it hands the buffer back to the controller.
+pub struct RxFrame<'d, 'a> {
+    rx: &'d mut RxRing<'a>,
+    info: RxFrameInfo,
+}
+
+impl RxFrame<'_, '_> {
+    pub fn info(&self) -> &RxFrameInfo {
+        &self.info
+    }
+
+    pub fn checksum(&self) -> RxChecksum {
+        self.info.checksum
+    }
+
+    pub fn vlan(&self) -> Option<VlanTag> {
+        self.info.vlan
+    }
+}
+
+impl Deref for RxFrame<'_, '_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.rx.frame(self.info.len)
+    }
+}
+
+impl DerefMut for RxFrame<'_, '_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        self.rx.buffer(self.info.len)
+    }
+}
+
+impl Drop for RxFrame<'_, '_> {
+    fn drop(&mut self) {
+        self.rx.release();
+    }
+}
+
+// A received frame spread over one or more DMA buffers. Software owns all of them for as long
+// as the chain is alive, dropping it hands them back to the controller.
+pub struct RxChain<'d, 'a> {
+    rx: &'d mut RxRing<'a>,
+    descriptors: usize,
+    info: RxFrameInfo,
+}
+
+impl<'d, 'a> RxChain<'d, 'a> {
+    pub fn len(&self) -> usize {
+        self.info.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.info.len == 0
+    }
+
+    pub fn info(&self) -> &RxFrameInfo {
+        &self.info
+    }
+
+    // Number of buffers the frame spans
+    pub fn descriptors(&self) -> usize {
+        self.descriptors
+    }
+
+    pub fn checksum(&self) -> RxChecksum {
+        self.info.checksum
+    }
+
+    pub fn vlan(&self) -> Option<VlanTag> {
+        self.info.vlan
+    }
+
+    // The frame data, one slice per buffer in order
+    pub fn segments(&self) -> impl Iterator<Item = &[u8]> + use<'_, 'd, 'a> {
+        (0..self.descriptors).map(|i| self.rx.segment(i, self.info.len))
+    }
+
+    pub fn copy_to(&self, buf: &mut [u8]) -> Result<usize, &'static str> {
+        if buf.len() < self.info.len {
+            return Err("Buffer too small for frame");
+        }
+        let mut copied = 0;
+        for segment in self.segments() {
+            buf[copied..copied + segment.len()].copy_from_slice(segment);
+            copied += segment.len();
+        }
+        Ok(copied)
+    }
+}
+
+impl Drop for RxChain<'_, '_> {
+    fn drop(&mut self) {
+        self.rx.release_n(self.descriptors);
+    }
+}
+
+// A frame queued from caller provided buffers. Holds on to them while the controller may still
+// be reading them, `Driver::complete` gives them back.
+#[derive(Debug)]
+#[must_use]
+pub struct TxInFlight<B = &'static mut [u8]> {
+    buf: B,
+    ring: usize,
+    seq: u64,
+}
+
+impl<B> TxInFlight<B> {
+    pub fn id(&self) -> TxFrameId {
+        TxFrameId(self.seq)
+    }
+}
+
+impl TxInFlight {
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+}
+
+// Lends the DMA buffer of one received frame, which is handed back to the controller once
+// consumed or dropped
+pub struct RxToken<'d, 'a> {
+    frame: RxFrame<'d, 'a>,
+}
+
+impl RxToken<'_, '_> {
+    pub fn len(&self) -> usize {
+        self.frame.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frame.is_empty()
+    }
+
+    pub fn info(&self) -> &RxFrameInfo {
+        self.frame.info()
+    }
+
+    pub fn checksum(&self) -> RxChecksum {
+        self.frame.checksum()
+    }
+
+    pub fn vlan(&self) -> Option<VlanTag> {
+        self.frame.vlan()
+    }
+
+    pub fn consume<R, F>(mut self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        f(&mut self.frame)
+    }
+}
+
+// Lends a free TX DMA buffer, which is queued for transmission once consumed
+pub struct TxToken<'d, 'a, I = AnyGem> {
+    dev: &'d Device<Running, I>,
+    tx: &'d mut TxRing<'a>,
+}
+
+impl<I> TxToken<'_, '_, I> {
+    // Largest frame a TX buffer holds
+    pub fn max_len(&self) -> usize {
+        self.tx.buf_size()
+    }
+
+    // A frame longer than `max_len` is refused without running `f`
+    pub fn consume<R, F>(self, len: usize, f: F) -> Result<R, &'static str>
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        if len > self.max_len() {
+            return Err("Frame larger than a TX buffer");
+        }
+        let result = f(self.tx.buffer(len));
+        self.tx.submit(len);
+        self.dev.start_tx();
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gem::sim::{driver, driver_with, GemSim, SimDma, SIM_BUF_SIZE, SIM_RING_LEN};
+    use crate::gem::{
+        CacheOps, DmaTranslation, GemConfig, GemInterrupts, InterruptCoalescing,
+        TX_COMPLETION_QUEUE,
+    };
+
+    use crate::gem::TxError;
+    use eth_phy::{Duplex, Speed};
+
+    extern crate std;
+    use core::cell::{Cell, RefCell};
+    use std::boxed::Box;
+    use std::vec::Vec;
+
+    const ETHERTYPE_TEST: [u8; 2] = [0x88, 0xB5];
+
+    fn frame(len: usize, seed: u8) -> [u8; 128] {
+        let mut frame = [0u8; 128];
+        frame[..6].copy_from_slice(&[0xFF; 6]);
+        frame[6..12].copy_from_slice(&[0x02, 0, 0, 0, 0, 1]);
+        frame[12..14].copy_from_slice(&ETHERTYPE_TEST);
+        for (i, byte) in frame[14..len].iter_mut().enumerate() {
+            *byte = seed.wrapping_add(i as u8);
+        }
+        frame
+    }
+
+    #[test]
+    fn transmit_reaches_the_wire() {
+        let mut sim = GemSim::new();
+        let mut driver = driver(&mut sim);
+
+        let data = frame(60, 1);
+        driver
+            .transmit(60, |buf| buf.copy_from_slice(&data[..60]))
+            .unwrap();
+        assert_eq!(driver.tx.in_flight(), 1);
+        assert_eq!(driver.reclaim_tx(), 0);
+
+        sim.step();
+        let sent = sim.take_tx().unwrap();
+        assert_eq!(sent.data, &data[..60]);
+        assert_eq!(sent.descriptors, 1);
+        assert!(sim
+            .pending_interrupts()
+            .contains(GemInterrupts::TX_COMPLETE));
+        assert_eq!(driver.reclaim_tx(), 1);
+
+        // A frame larger than a buffer is refused before it takes a descriptor
+        assert!(driver.transmit(SIM_BUF_SIZE + 1, |_| ()).is_none());
+        let token = driver.transmit_token().unwrap();
+        assert_eq!(token.max_len(), SIM_BUF_SIZE);
+        assert!(token.consume(SIM_BUF_SIZE + 1, |_| ()).is_err());
+        assert_eq!(driver.tx.in_flight(), 0);
+        sim.step();
+        assert!(sim.take_tx().is_none());
+    }
+
+    #[test]
+    fn receive_recycles_buffers() {
+        let mut sim = GemSim::new();
+        let mut driver = driver(&mut sim);
+
+        // One more frame than the ring holds, the last waits for a free descriptor
+        for i in 0..=SIM_RING_LEN {
+            sim.inject_rx(&frame(64, i as u8)[..64]);
+        }
+        sim.step();
+        assert_eq!(sim.rx_queued(), 1);
+        assert!(sim
+            .pending_interrupts()
+            .contains(GemInterrupts::RX_COMPLETE | GemInterrupts::RX_USED_BIT_READ));
+
+        for i in 0..SIM_RING_LEN {
+            let rx = driver.receive().unwrap();
+            assert_eq!(rx.len(), 64);
+            assert_eq!(&rx[..], &frame(64, i as u8)[..64]);
+        }
+        assert!(driver.receive().is_none());
+
+        sim.step();
+        assert_eq!(sim.rx_queued(), 0);
+        let (rx, _) = driver.receive_tokens().unwrap();
+        rx.consume(|buf| assert_eq!(buf, &frame(64, SIM_RING_LEN as u8)[..64]));
+    }
+
+    #[test]
+    fn caller_buffers_returned_once_sent() {
+        let mut sim = GemSim::new();
+        let mut driver = driver(&mut sim);
+
+        let buf = Box::leak(Box::new(frame(100, 7)));
+        let in_flight = driver.transmit_buffer(&mut buf[..100]).unwrap();
+        let in_flight = driver.complete(in_flight).unwrap_err();
+
+        sim.step();
+        let sent = sim.take_tx().unwrap();
+        assert_eq!(sent.data, &frame(100, 7)[..100]);
+        let buf = driver.complete(in_flight).unwrap();
+        assert_eq!(buf.len(), 100);
+
+        // The ring's own buffers are used again afterwards
+        driver.transmit(60, |buf| buf.fill(0xA5)).unwrap();
+        sim.step();
+        assert_eq!(sim.take_tx().unwrap().data, [0xA5; 60]);
+        assert!(driver.transmit_buffer(&mut []).is_err());
+    }
+
+    #[test]
+    fn gathered_frames_complete_as_one() {
+        let mut sim = GemSim::new();
+        let mut driver = driver(&mut sim);
+
+        let buf: &'static mut [u8] = Box::leak(Box::new([0x5A; 512]));
+        let (first_header, buf) = buf.split_at_mut(14);
+        let (first_payload, buf) = buf.split_at_mut(200);
+        let (second_header, buf) = buf.split_at_mut(14);
+        let (second_payload, spare) = buf.split_at_mut(200);
+        let header = &frame(14, 0)[..14];
+        first_header.copy_from_slice(header);
+        second_header.copy_from_slice(header);
+
+        let first = driver
+            .transmit_sg([first_header, &mut [], first_payload])
+            .unwrap();
+        sim.step();
+        sim.fail_next_tx(tx_desc_status::RETRY_LIMIT_EXCEEDED::SET);
+        let second = driver.transmit_sg([second_header, second_payload]).unwrap();
+        assert_eq!(driver.tx.in_flight(), 4);
+        sim.step();
+
+        let sent = sim.take_tx().unwrap();
+        assert!(sim.take_tx().is_none());
+        assert_eq!(sent.descriptors, 2);
+        assert_eq!(&sent.data[..14], header);
+        assert_eq!(&sent.data[14..], [0x5A; 200]);
+
+        // The buffers come back once sent, the completion waits for `reclaim_tx_with`
+        let (first_id, second_id) = (first.id(), second.id());
+        let [h, _, payload] = driver.complete(first).unwrap();
+        assert_eq!((h.len(), payload.len()), (14, 200));
+        let mut completions = Vec::new();
+        assert_eq!(driver.reclaim_tx_with(|c| completions.push(c)), 2);
+        assert_eq!(
+            completions,
+            [
+                TxCompletion {
+                    id: first_id,
+                    descriptors: 2,
+                    result: Ok(()),
+                },
+                TxCompletion {
+                    id: second_id,
+                    descriptors: 2,
+                    result: Err(TxError::RetryLimitExceeded),
+                },
+            ]
+        );
+        assert_eq!(driver.tx.in_flight(), 0);
+        assert!(driver.complete(second).is_ok());
+
+        // Whole frames only, so a frame larger than the ring is refused outright
+        let mut bytes = spare.chunks_mut(1);
+        let parts: [&'static mut [u8]; SIM_RING_LEN + 1] =
+            core::array::from_fn(|_| bytes.next().unwrap());
+        assert!(driver.transmit_sg(parts).is_err());
+        assert!(driver.transmit_sg([]).is_err());
+    }
+
+    #[test]
+    fn completions_kept_for_reclaim() {
+        let mut sim = GemSim::new();
+        let mut driver = driver(&mut sim);
+
+        // Frames retired to make room are reported later, in order
+        let mut completions = Vec::new();
+        for i in 0..SIM_RING_LEN + 2 {
+            if i == SIM_RING_LEN {
+                sim.step();
+            }
+            driver.transmit(60, |buf| buf.fill(i as u8)).unwrap();
+        }
+        assert_eq!(
+            driver.reclaim_tx_with(|c| completions.push(c.id)),
+            SIM_RING_LEN
+        );
+        sim.step();
+        assert_eq!(driver.reclaim_tx_with(|c| completions.push(c.id)), 2);
+        assert!(completions.windows(2).all(|ids| ids[0] < ids[1]));
+
+        // Beyond the queue the oldest are dropped, and counted
+        for _ in 0..TX_COMPLETION_QUEUE / SIM_RING_LEN + 1 {
+            for _ in 0..SIM_RING_LEN {
+                driver.transmit(60, |_| ()).unwrap();
+            }
+            sim.step();
+            while sim.take_tx().is_some() {}
+        }
+        driver.transmit(60, |_| ()).unwrap();
+        assert_eq!(driver.stats().tx_completions_dropped, SIM_RING_LEN as u64);
+        assert_eq!(driver.reclaim_tx(), TX_COMPLETION_QUEUE);
+    }
+
+    #[test]
+    fn chained_frames_reassembled() {
+        let mut sim = GemSim::new();
+        let mut driver = driver(&mut sim);
+
+        let big: Vec<u8> = (0..3000).map(|i| i as u8).collect();
+        sim.inject_rx_aborted(&big[..2000]);
+        sim.inject_rx(&big);
+        sim.inject_rx(&frame(64, 3)[..64]);
+        sim.step();
+
+        // The abandoned frame is recycled on the way to the complete one
+        let chain = driver.receive_chain().unwrap();
+        assert_eq!((chain.len(), chain.descriptors()), (3000, 2));
+        let lens: Vec<usize> = chain.segments().map(|s| s.len()).collect();
+        assert_eq!(lens, [SIM_BUF_SIZE, 3000 - SIM_BUF_SIZE]);
+        let mut copy = [0u8; 3000];
+        assert_eq!(chain.copy_to(&mut copy), Ok(3000));
+        assert_eq!(&copy[..], &big[..]);
+        assert!(chain.copy_to(&mut copy[..100]).is_err());
+        drop(chain);
+
+        assert_eq!(&driver.receive().unwrap()[..], &frame(64, 3)[..64]);
+
+        // Every descriptor went back, a full ring of large frames fits again
+        for _ in 0..SIM_RING_LEN / 2 {
+            sim.inject_rx(&big);
+        }
+        sim.step();
+        assert_eq!(sim.rx_queued(), 0);
+        for _ in 0..SIM_RING_LEN / 2 {
+            let mut copy = [0u8; 3000];
+            assert_eq!(driver.receive_into(&mut copy), Some(Ok(3000)));
+        }
+        assert!(driver.receive_into(&mut [0u8; 64]).is_none());
+    }
+
+    #[test]
+    fn tx_recovers_from_bus_error() {
+        let mut sim = GemSim::new();
+        let mut driver = driver(&mut sim);
+
+        sim.fail_next_tx(tx_desc_status::AMBA_ERROR::SET);
+        for i in 0..3 {
+            driver.transmit(60, |buf| buf.fill(i)).unwrap();
+        }
+        sim.step();
+        assert!(sim.take_tx().is_none());
+        assert_eq!(driver.reclaim_tx(), 1);
+        assert_eq!(driver.tx.in_flight(), 2);
+
+        // Nothing is touched while the transmitter fails to halt
+        let status = sim.read_reg(0x14);
+        sim.write_reg(0x14, status | transmit_status::TRANSMIT_GO::SET.value);
+        assert!(driver.recover_tx(|_| ()).is_err());
+        assert_eq!(driver.tx.in_flight(), 2);
+        sim.write_reg(0x14, status);
+
+        // The first frame is already retired, the other two never went out
+        let mut results = Vec::new();
+        let status = driver.poll_tx(|c| results.push(c.result)).unwrap();
+        assert!(status.contains(TxStatus::AMBA_ERROR));
+        assert_eq!(results, [Err(TxError::Aborted), Err(TxError::Aborted)]);
+        assert_eq!(driver.tx.in_flight(), 0);
+
+        sim.step();
+        assert!(!driver
+            .device()
+            .get_transmit_status()
+            .intersects(TxStatus::ERRORS));
+        driver.transmit(60, |buf| buf.fill(0xEE)).unwrap();
+        sim.step();
+        assert_eq!(sim.take_tx().unwrap().data, [0xEE; 60]);
+        assert_eq!(
+            driver.device().get_tx_desc() as usize,
+            driver.tx.base_addr() as u32 as usize + 8
+        );
+    }
+
+    #[test]
+    fn aborted_frames_reported_later() {
+        let mut sim = GemSim::new();
+        let mut driver = driver(&mut sim);
+
+        // A full ring behind an error is recovered to make room
+        sim.fail_next_tx(tx_desc_status::AMBA_ERROR::SET);
+        for i in 0..SIM_RING_LEN + 1 {
+            if i == SIM_RING_LEN {
+                sim.step();
+            }
+            driver.transmit(60, |_| ()).unwrap();
+        }
+        driver.transmit(60, |_| ()).unwrap();
+        let mut results = Vec::new();
+        driver.reclaim_tx_with(|c| results.push(c.result));
+        assert_eq!(results.len(), SIM_RING_LEN + 1);
+        assert!(results[0].is_err());
+        assert!(results[1..].iter().all(|r| *r == Err(TxError::Aborted)));
+
+        // As are the frames in flight when the link changes
+        driver.transmit(60, |_| ()).unwrap();
+        driver.apply_link(&LinkStatus {
+            speed: Speed::S100,
+            duplex: Duplex::Full,
+            pause: false,
+        });
+        results.clear();
+        driver.reclaim_tx_with(|c| results.push(c.result));
+        assert_eq!(results, [Err(TxError::Aborted), Err(TxError::Aborted)]);
+        assert_eq!(driver.tx.in_flight(), 0);
+    }
+
+    #[test]
+    fn rx_recovers_from_stalls_and_bus_errors() {
+        let mut sim = GemSim::new();
+        let mut driver = driver(&mut sim);
+
+        // A frame stuck in the packet buffer is only flushed once reported twice with
+        // descriptors free
+        sim.stall_rx();
+        sim.inject_rx(&frame(64, 1)[..64]);
+        sim.inject_rx(&frame(64, 2)[..64]);
+        sim.step();
+        assert!(driver.receive().is_none());
+        assert_eq!(driver.stats().rx_flushed, 0);
+        sim.step();
+        assert!(driver.receive().is_none());
+        sim.step();
+        assert_eq!(sim.rx_queued(), 0);
+        assert_eq!(&driver.receive().unwrap()[..], &frame(64, 2)[..64]);
+        assert_eq!(driver.stats().rx_buffers_unavailable, 2);
+        assert_eq!(driver.stats().rx_flushed, 1);
+
+        // Frames received before a bus error are still handed out, then the ring starts over
+        sim.inject_rx(&frame(64, 3)[..64]);
+        sim.step();
+        sim.fail_rx_dma();
+        sim.inject_rx(&frame(64, 4)[..64]);
+        sim.step();
+        assert_eq!(&driver.receive().unwrap()[..], &frame(64, 3)[..64]);
+        assert!(driver.receive().is_none());
+        assert_eq!(sim.rx_queued(), 1);
+        sim.step();
+        assert_eq!(&driver.receive().unwrap()[..], &frame(64, 4)[..64]);
+        assert_eq!(
+            driver.device().receive_q_ptr.get() as usize,
+            driver.rx.base_addr() as u32 as usize + 8
+        );
+        assert_eq!(
+            driver.stats(),
+            DriverStats {
+                rx_buffers_unavailable: 2,
+                rx_flushed: 1,
+                rx_bus_errors: 1,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn poll_stops_at_budget() {
+        let mut sim = GemSim::new();
+        let (rx, tx) = SimDma::leak().rings();
+        let config = GemConfig::new().interrupt_coalescing(InterruptCoalescing::new(10, 20));
+        let mut driver = driver_with(&mut sim, config, rx, tx);
+        assert_eq!(sim.read_reg(0x5C), 0x0014_000A);
+        driver
+            .device()
+            .set_interrupt_coalescing(InterruptCoalescing::new(0, 5));
+        assert_eq!(
+            driver.device().interrupt_coalescing(),
+            InterruptCoalescing::new(0, 5)
+        );
+
+        for i in 0..5 {
+            sim.inject_rx(&frame(64, i)[..64]);
+        }
+        sim.step();
+        let mut seeds = Vec::new();
+        assert_eq!(driver.poll(3, |rx| seeds.push(rx[14])), 3);
+        assert_eq!(driver.poll(3, |rx| seeds.push(rx[14])), 2);
+        assert_eq!(driver.poll(3, |_| ()), 0);
+        assert_eq!(seeds, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn translated_addresses_reach_memory() {
+        fn shifted(addr: usize) -> u64 {
+            addr as u64 + 0x1000
+        }
+
+        let mut sim = GemSim::new();
+        sim.set_bus_offset(0x1000);
+        let (rx, tx) = SimDma::leak().rings();
+        let rx = rx.with_translation(DmaTranslation::Offset(0x1000)).unwrap();
+        let tx = tx.with_translation(DmaTranslation::Map(&shifted)).unwrap();
+        let mut driver = driver_with(&mut sim, GemConfig::new(), rx, tx);
+        assert_eq!(
+            driver.device().receive_q_ptr.get(),
+            driver.rx.base_addr() as u32 + 0x1000
+        );
+
+        sim.inject_rx(&frame(64, 9)[..64]);
+        sim.step();
+        assert_eq!(&driver.receive().unwrap()[..], &frame(64, 9)[..64]);
+
+        let header: &'static mut [u8] = Box::leak(Box::new(frame(14, 0)));
+        let payload: &'static mut [u8] = Box::leak(Box::new([0x22; 46]));
+        driver.transmit(60, |buf| buf.fill(0x11)).unwrap();
+        let _ = driver.transmit_sg([&mut header[..14], payload]).unwrap();
+        sim.step();
+        assert_eq!(sim.take_tx().unwrap().data, [0x11; 60]);
+        assert_eq!(&sim.take_tx().unwrap().data[14..], [0x22; 46]);
+    }
+
+    #[test]
+    fn rings_checked_against_the_device() {
+        let sim = GemSim::new();
+        let dev = || unsafe { Device::new(sim.ptr()) };
+
+        // RX buffers of another size than configured
+        let (rx, tx) = SimDma::leak().rings();
+        let config = dev().init(GemConfig::new()).unwrap().phy_complete();
+        assert!(Driver::new(config, rx, tx).is_err());
+
+        // Different OR masks for the two directions
+        let (rx, tx) = SimDma::leak().rings();
+        let tx = tx
+            .with_translation(DmaTranslation::OrMask {
+                value: 1,
+                enable: 1,
+            })
+            .unwrap();
+        let config = dev()
+            .init(GemConfig::new().rx_buf_size(SIM_BUF_SIZE))
+            .unwrap()
+            .phy_complete();
+        assert!(Driver::new(config, rx, tx).is_err());
+
+        // Buffers the descriptors cannot address, in another 4 GiB window than the ring
+        let (_, tx) = SimDma::leak().rings();
+        let descs = tx.base_addr();
+        let split = move |addr: usize| match addr == descs {
+            true => addr as u64,
+            false => addr as u64 + (1 << 32),
+        };
+        let split: &'static dyn Fn(usize) -> u64 = Box::leak(Box::new(split));
+        assert!(tx.with_translation(DmaTranslation::Map(split)).is_err());
+    }
+
+    #[test]
+    fn ownership_handoffs_maintain_caches() {
+        // (invalidated, address, length) of each call
+        #[derive(Default)]
+        struct Recorder(RefCell<Vec<(bool, usize, usize)>>);
+
+        impl CacheOps for Recorder {
+            fn clean(&self, addr: usize, len: usize) {
+                self.0.borrow_mut().push((false, addr, len));
+            }
+
+            fn clean_invalidate(&self, addr: usize, len: usize) {
+                self.0.borrow_mut().push((true, addr, len));
+            }
+        }
+
+        let mut sim = GemSim::new();
+        let dma = SimDma::leak();
+        let rx_buf = dma.rx_bufs.as_ptr() as usize;
+        let tx_buf = dma.tx_bufs.as_ptr() as usize;
+        let cache: &'static Recorder = Box::leak(Box::default());
+        let (rx, tx) = dma.rings();
+        // Safety: the simulated controller is coherent
+        let (rx, tx) = unsafe { (rx.with_cache(cache), tx.with_cache(cache)) };
+        cache.0.take();
+        let mut driver = driver_with(&mut sim, GemConfig::new(), rx, tx);
+        assert_eq!(
+            cache.0.take(),
+            [(true, rx_buf, SIM_RING_LEN * SIM_BUF_SIZE)]
+        );
+
+        // Only buffers are maintained: data is fetched before the frame is read, and the
+        // buffer handed back clean
+        sim.inject_rx(&frame(64, 0)[..64]);
+        sim.step();
+        drop(driver.receive().unwrap());
+        assert_eq!(
+            cache.0.take(),
+            [(true, rx_buf, SIM_BUF_SIZE), (true, rx_buf, SIM_BUF_SIZE)]
+        );
+
+        driver.transmit(60, |buf| buf.fill(0x33)).unwrap();
+        assert_eq!(cache.0.take(), [(false, tx_buf, 60)]);
+        sim.step();
+        assert_eq!(driver.reclaim_tx(), 1);
+        assert!(cache.0.take().is_empty());
+    }
+
+    #[test]
+    fn neighbour_descriptors_left_alone() {
+        // Lets the controller finish the previous frame while a buffer is cleaned, so its
+        // descriptor changes in the same cache line as the one being written
+        struct Racing {
+            sim: RefCell<GemSim>,
+            race: Cell<bool>,
+            ops: RefCell<Vec<(usize, usize)>>,
+        }
+
+        impl CacheOps for Racing {
+            fn clean(&self, addr: usize, len: usize) {
+                self.ops.borrow_mut().push((addr, len));
+                if self.race.take() {
+                    self.sim.borrow_mut().step();
+                }
+            }
+
+            fn clean_invalidate(&self, addr: usize, len: usize) {
+                self.ops.borrow_mut().push((addr, len));
+            }
+        }
+
+        let cache: &'static Racing = Box::leak(Box::new(Racing {
+            sim: RefCell::new(GemSim::new()),
+            race: Cell::new(false),
+            ops: RefCell::default(),
+        }));
+        let dma = SimDma::leak();
+        let descs = [
+            dma.rx_descs.as_ptr() as usize..dma.rx_descs.as_ptr_range().end as usize,
+            dma.tx_descs.as_ptr() as usize..dma.tx_descs.as_ptr_range().end as usize,
+        ];
+        let (rx, tx) = dma.rings();
+        // Safety: the simulated controller is coherent
+        let (rx, tx) = unsafe { (rx.with_cache(cache), tx.with_cache(cache)) };
+        let mut driver = driver_with(&mut cache.sim.borrow_mut(), GemConfig::new(), rx, tx);
+
+        driver.transmit(60, |buf| buf.fill(1)).unwrap();
+        cache.race.set(true);
+        driver.transmit(60, |buf| buf.fill(2)).unwrap();
+        assert!(!cache.race.get());
+        cache.sim.borrow_mut().step();
+
+        // The first completion survives the second frame being queued next to it
+        assert_eq!(driver.reclaim_tx(), 2);
+        let mut sim = cache.sim.borrow_mut();
+        assert_eq!(sim.take_tx().unwrap().data, [1; 60]);
+        assert_eq!(sim.take_tx().unwrap().data, [2; 60]);
+        assert!(cache
+            .ops
+            .borrow()
+            .iter()
+            .all(|&(addr, len)| descs.iter().all(|d| addr + len <= d.start || addr >= d.end)));
+    }
+
+    #[test]
+    fn interrupts_acknowledged_selectively() {
+        let mut sim = GemSim::new();
+        let driver = driver(&mut sim);
+        let dev = driver.device();
+
+        dev.enable_interrupts(GemInterrupts::RX_EVENTS | GemInterrupts::LINK_CHANGE);
+        sim.raise(GemInterrupts::LINK_CHANGE);
+        sim.inject_rx(&frame(64, 0)[..64]);
+        sim.step();
+        assert!(sim.irq());
+
+        let handled = dev.handle_interrupts(|pending| pending & GemInterrupts::RX_COMPLETE);
+        assert_eq!(handled, GemInterrupts::RX_COMPLETE);
+        sim.step();
+        assert_eq!(sim.pending_interrupts(), GemInterrupts::LINK_CHANGE);
+
+        dev.disable_interrupts(GemInterrupts::LINK_CHANGE);
+        sim.step();
+        assert!(!sim.irq());
+    }
+
+    #[test]
+    fn dropped_device_stops_dma() {
+        let mut sim = GemSim::new();
+        let mut driver = driver(&mut sim);
+        driver.device().enable_interrupts(GemInterrupts::RX_EVENTS);
+        sim.inject_rx(&frame(64, 1)[..64]);
+        driver.transmit(64, |buf| buf.fill(0x44)).unwrap();
+        sim.step();
+        assert_eq!(&driver.receive().unwrap()[..], &frame(64, 1)[..64]);
+        let status = sim.read_reg(0x14);
+        sim.write_reg(0x14, status | transmit_status::TRANSMIT_GO::SET.value);
+        let Err(driver) = driver.shutdown() else {
+            panic!("shut down while transmitting");
+        };
+        sim.write_reg(0x14, status);
+        let Ok((dev, rx, tx)) = driver.shutdown() else {
+            panic!("not shut down");
+        };
+        sim.step();
+        assert_eq!(sim.take_tx().unwrap().data, [0x44; 64]);
+        assert_eq!(sim.read_reg(0x30), GemInterrupts::all().bits());
+
+        // Through reset and back up on the same rings
+        let dev = dev
+            .reset()
+            .init(GemConfig::new().rx_buf_size(SIM_BUF_SIZE))
+            .unwrap();
+        sim.step();
+        let mut driver = Driver::new(dev.phy_complete(), rx, tx).unwrap();
+        driver.device().enable_interrupts(GemInterrupts::RX_EVENTS);
+        sim.step();
+        sim.inject_rx(&frame(64, 2)[..64]);
+        driver.transmit(64, |buf| buf.fill(0x55)).unwrap();
+        sim.step();
+        assert_eq!(sim.take_tx().unwrap().data, [0x55; 64]);
+        assert_eq!(&driver.receive().unwrap()[..], &frame(64, 2)[..64]);
+        drop(driver);
+        sim.inject_rx(&frame(64, 0)[..64]);
+        sim.step();
+        assert_eq!(sim.rx_queued(), 1);
+        assert!(!sim.irq());
+
+        // A leaked device keeps receiving into the rings it was given
+        let dev = unsafe { Device::new(sim.ptr()) }
+            .init(GemConfig::new().rx_buf_size(SIM_BUF_SIZE))
+            .unwrap()
+            .phy_complete()
+            .run();
+        dev.leak();
+        sim.step();
+        assert_eq!(sim.rx_queued(), 0);
+    }
+}
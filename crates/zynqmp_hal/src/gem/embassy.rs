@@ -0,0 +1,339 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use core::marker::PhantomData;
+use core::mem::ManuallyDrop;
+use core::ptr::null_mut;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, Ordering};
+use core::task::Context;
+
+use embassy_net_driver::{
+    Capabilities, Checksum, ChecksumCapabilities, HardwareAddress, LinkState,
+};
+use embassy_sync::waitqueue::AtomicWaker;
+
+use eth_phy::{Duplex, GenPhy, LinkStatus, PhyReadWrite, Speed};
+use zynqmp_pac::gem::RegisterBlock;
+
+use super::checksum::{checksum_capabilities, ChecksumOffload};
+use super::driver::{Driver, RxToken, TxToken};
+use super::link::{LinkEvent, LinkSupervisor};
+use super::{AnyGem, Device, GemInterrupts, Running};
+
+// Interrupt sources the driver acknowledges, anything else is left pending for the application
+const HANDLED: GemInterrupts = GemInterrupts::RX_EVENTS
+    .union(GemInterrupts::TX_EVENTS)
+    .union(GemInterrupts::LINK_CHANGE);
+
+// A link in one byte, set while the driver still has to program the MAC for it
+const LINK_PENDING: u8 = 1 << 7;
+const LINK_FULL_DUPLEX: u8 = 1 << 2;
+const LINK_PAUSE: u8 = 1 << 3;
+
+fn pack_link(link: &LinkStatus) -> u8 {
+    let speed = match link.speed {
+        Speed::S10 => 0,
+        Speed::S100 => 1,
+        Speed::S1000 => 2,
+    };
+    let mut packed = LINK_PENDING | speed;
+    if link.duplex == Duplex::Full {
+        packed |= LINK_FULL_DUPLEX;
+    }
+    if link.pause {
+        packed |= LINK_PAUSE;
+    }
+    packed
+}
+
+fn unpack_link(packed: u8) -> Option<LinkStatus> {
+    if packed & LINK_PENDING == 0 {
+        return None;
+    }
+    Some(LinkStatus {
+        speed: match packed & 3 {
+            0 => Speed::S10,
+            1 => Speed::S100,
+            _ => Speed::S1000,
+        },
+        duplex: match packed & LINK_FULL_DUPLEX {
+            0 => Duplex::Half,
+            _ => Duplex::Full,
+        },
+        pause: packed & LINK_PAUSE != 0,
+    })
+}
+
+// Shared between the interrupt handler and the driver. Typically placed in a static so the
+// interrupt vector can reach it.
+pub struct State {
+    regs: AtomicPtr<RegisterBlock>,
+    rx_waker: AtomicWaker,
+    tx_waker: AtomicWaker,
+    link_waker: AtomicWaker,
+    link_up: AtomicBool,
+    // From `supervise_link`, see `pack_link`
+    pending_link: AtomicU8,
+    // Reception or transmission ran into an error, the driver recovers on its next pass
+    rx_error: AtomicBool,
+    tx_error: AtomicBool,
+}
+
+impl State {
+    pub const fn new() -> Self {
+        Self {
+            regs: AtomicPtr::new(null_mut()),
+            rx_waker: AtomicWaker::new(),
+            tx_waker: AtomicWaker::new(),
+            link_waker: AtomicWaker::new(),
+            link_up: AtomicBool::new(false),
+            pending_link: AtomicU8::new(0),
+            rx_error: AtomicBool::new(false),
+            tx_error: AtomicBool::new(false),
+        }
+    }
+
+    // Call from the GEM interrupt vector. Wakes the RX, TX and link tasks and acknowledges only
+    // the interrupt sources it handled.
+    pub fn on_interrupt(&self) {
+        let regs = self.regs.load(Ordering::Acquire);
+        if regs.is_null() {
+            return;
+        }
+        // A view of the device the driver owns, which must not stop it when dropped
+        let dev: ManuallyDrop<Device<Running>> = ManuallyDrop::new(Device {
+            ptr: regs,
+            phantom: PhantomData,
+        });
+
+        dev.handle_interrupts(|pending| {
+            if pending.intersects(GemInterrupts::RX_EVENTS) {
+                self.rx_waker.wake();
+            }
+            if pending.intersects(GemInterrupts::RX_ERRORS) {
+                self.rx_error.store(true, Ordering::Release);
+            }
+            if pending.intersects(GemInterrupts::TX_ERRORS) {
+                self.tx_error.store(true, Ordering::Release);
+            }
+            if pending.intersects(GemInterrupts::TX_EVENTS) {
+                self.tx_waker.wake();
+            }
+            if pending.intersects(GemInterrupts::LINK_CHANGE) {
+                self.link_waker.wake();
+            }
+            pending & HANDLED
+        });
+    }
+
+    pub fn set_link_state(&self, state: LinkState) {
+        let up = state == LinkState::Up;
+        if self.link_up.swap(up, Ordering::AcqRel) != up {
+            self.link_waker.wake();
+        }
+    }
+
+    // Run the link supervisor from a task polling the PHY, e.g. woken by the PHY interrupt or a
+    // timer. The driver reprograms the MAC for a link which came up or changed the next time
+    // the network stack asks for the link state, and only then reports it up.
+    pub fn supervise_link<T: PhyReadWrite>(
+        &self,
+        supervisor: &mut LinkSupervisor,
+        phy: &GenPhy<T>,
+    ) -> Option<LinkEvent> {
+        let event = supervisor.check(phy);
+        match &event {
+            Some(LinkEvent::Up(link) | LinkEvent::Changed(link)) => {
+                self.link_up.store(false, Ordering::Release);
+                self.pending_link.store(pack_link(link), Ordering::Release);
+                self.link_waker.wake();
+            }
+            Some(LinkEvent::Down) => {
+                self.pending_link.store(0, Ordering::Release);
+                self.set_link_state(LinkState::Down);
+            }
+            None => (),
+        }
+        event
+    }
+
+    fn link_state(&self) -> LinkState {
+        match self.link_up.load(Ordering::Acquire) {
+            true => LinkState::Up,
+            false => LinkState::Down,
+        }
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct AsyncDriver<'a, I = AnyGem> {
+    driver: Driver<'a, I>,
+    state: &'a State,
+}
+
+impl<'a, I> AsyncDriver<'a, I> {
+    pub fn new(driver: Driver<'a, I>, state: &'a State) -> Self {
+        let dev = driver.device();
+        state.regs.store(dev.ptr(), Ordering::Release);
+        dev.enable_interrupts(HANDLED);
+        Self { driver, state }
+    }
+
+    // Program the MAC for the link `State::supervise_link` last saw come up
+    fn follow_link(&mut self) {
+        let packed = self.state.pending_link.swap(0, Ordering::AcqRel);
+        if let Some(link) = unpack_link(packed) {
+            self.driver.apply_link(&link);
+            self.state.set_link_state(LinkState::Up);
+        }
+    }
+
+    fn recover_rx(&mut self) {
+        if self.state.rx_error.swap(false, Ordering::AcqRel) {
+            self.driver.poll_rx();
+        }
+    }
+
+    fn recover_tx(&mut self) {
+        if self.state.tx_error.swap(false, Ordering::AcqRel)
+            && self.driver.poll_tx_queued().is_err()
+        {
+            // Try again next time round
+            self.state.tx_error.store(true, Ordering::Release);
+        }
+    }
+
+    pub fn release(self) -> Driver<'a, I> {
+        let dev = self.driver.device();
+        dev.disable_interrupts(HANDLED);
+        self.state.regs.store(null_mut(), Ordering::Release);
+        self.driver
+    }
+}
+
+impl<'a, I> embassy_net_driver::Driver for AsyncDriver<'a, I> {
+    type RxToken<'d>
+        = RxToken<'d, 'a>
+    where
+        Self: 'd;
+    type TxToken<'d>
+        = TxToken<'d, 'a, I>
+    where
+        Self: 'd;
+
+    fn receive(&mut self, cx: &mut Context) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        self.state.rx_waker.register(cx.waker());
+        self.state.tx_waker.register(cx.waker());
+        self.recover_rx();
+        self.recover_tx();
+        self.driver.receive_tokens()
+    }
+
+    fn transmit(&mut self, cx: &mut Context) -> Option<Self::TxToken<'_>> {
+        self.state.tx_waker.register(cx.waker());
+        self.recover_tx();
+        self.driver.transmit_token()
+    }
+
+    fn link_state(&mut self, cx: &mut Context) -> LinkState {
+        self.state.link_waker.register(cx.waker());
+        self.follow_link();
+        self.state.link_state()
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        let checksum = checksum_capabilities(self.driver.checksum_offload());
+
+        let mut caps = Capabilities::default();
+        caps.max_transmission_unit = self.driver.mtu();
+        caps.max_burst_size = Some(self.driver.tx_ring_len());
+        caps.checksum = checksum;
+        caps
+    }
+
+    fn hardware_address(&self) -> HardwareAddress {
+        HardwareAddress::Ethernet(self.driver.mac_address().inner())
+    }
+}
+
+checksum_capabilities!(Checksum, ChecksumCapabilities);
+
+impl embassy_net_driver::RxToken for RxToken<'_, '_> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        RxToken::consume(self, f)
+    }
+}
+
+impl<I> embassy_net_driver::TxToken for TxToken<'_, '_, I> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        // The stack keeps to the MTU reported, which always fits a TX buffer
+        TxToken::consume(self, len, f).expect("frame larger than the MTU")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gem::sim::{driver, GemSim};
+    use embassy_net_driver::Driver as _;
+    use eth_phy::sim::{LinkPartner, SimMdioBus, SimPhy};
+    use eth_phy::Supported;
+    use zynqmp_pac::gem::network_config;
+
+    use core::task::Waker;
+    use tock_registers::interfaces::Readable;
+
+    extern crate std;
+    use std::boxed::Box;
+
+    #[test]
+    fn link_supervised_after_handover() {
+        let mut sim = GemSim::new();
+        let driver = driver(&mut sim);
+        let state: &'static State = Box::leak(Box::default());
+        let mut driver = AsyncDriver::new(driver, state);
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let bus = SimMdioBus::new();
+        bus.add_phy(0, SimPhy::new());
+        let phy = GenPhy::new(0, &bus, Supported::default());
+        let mut supervisor = LinkSupervisor::new();
+        assert_eq!(state.supervise_link(&mut supervisor, &phy), None);
+        assert!(driver.link_state(&mut cx) == LinkState::Down);
+
+        // The MAC is programmed for the link before the stack sees it up
+        bus.with_phy(0, |p| {
+            p.set_link_partner(Some(LinkPartner::up_to(Speed::S100, Duplex::Full, false)))
+        });
+        assert!(matches!(
+            state.supervise_link(&mut supervisor, &phy),
+            Some(LinkEvent::Up(_))
+        ));
+        let config = driver.driver.device().network_config.extract();
+        assert!(!config.is_set(network_config::SPEED));
+        assert!(driver.link_state(&mut cx) == LinkState::Up);
+        let config = driver.driver.device().network_config.extract();
+        assert!(config.is_set(network_config::SPEED) && config.is_set(network_config::FULL_DUPLEX));
+
+        bus.with_phy(0, |p| p.set_link_partner(None));
+        assert_eq!(
+            state.supervise_link(&mut supervisor, &phy),
+            Some(LinkEvent::Down)
+        );
+        assert!(driver.link_state(&mut cx) == LinkState::Down);
+    }
+}
@@ -0,0 +1,227 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+// Receive address filtering, which may change while the controller is running, e.g. for a new
+// MAC address on failover or a multicast group joined. Every filter is a single register,
+// except the specific addresses: writing the bottom half disables the filter until the top half
+// is written, so the controller never matches half of an old and half of a new address. None of
+// them needs receive disabled.
+
+use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
+
+use zynqmp_pac::gem::*;
+
+use super::{Config, Device, DeviceState, MacAddress, Running};
+
+// Specific address filters besides the station address in spec_add1
+pub const EXTRA_ADDRESS_FILTERS: usize = 3;
+
+// States in which the receive filters can be changed
+pub trait Filterable: DeviceState {}
+
+impl Filterable for Config {}
+impl Filterable for Running {}
+
+// Bit of the 64 bit hash filter a destination address selects: bit `i` of the index is the XOR
+// of every sixth address bit from bit `i`, counting from the least significant bit of the first
+// byte on the wire
+pub fn multicast_hash_index(mac: &MacAddress) -> u8 {
+    let mac = mac.inner();
+    (0..48).fold(0, |index, bit| {
+        index ^ (((mac[bit / 8] >> (bit % 8)) & 1) << (bit % 6))
+    })
+}
+
+impl<S: Filterable, I> Device<S, I> {
+    pub fn set_mac_address(&self, mac: MacAddress) {
+        self.spec_add1_bottom
+            .write(spec_add1_bottom::ADDRESS.val(mac.get_bottom()));
+        self.spec_add1_top
+            .write(spec_add1_top::ADDRESS.val(mac.get_top().into()));
+    }
+
+    pub fn split_mac_address(&self) -> (u32, u32) {
+        let bottom = self.spec_add1_bottom.read(spec_add1_bottom::ADDRESS);
+        let top = self.spec_add1_top.read(spec_add1_top::ADDRESS);
+        (bottom, top)
+    }
+
+    pub fn mac_address(&self) -> MacAddress {
+        let bottom = self.spec_add1_bottom.read(spec_add1_bottom::ADDRESS);
+        let top = self.spec_add1_top.read(spec_add1_top::ADDRESS) as u16;
+        MacAddress::from((bottom, top))
+    }
+
+    // Also receive frames to `mac`, in one of `EXTRA_ADDRESS_FILTERS` slots, or stop with
+    // `None`
+    pub fn set_extra_address(
+        &self,
+        slot: usize,
+        mac: Option<MacAddress>,
+    ) -> Result<(), &'static str> {
+        // Left disabled by `None`, until the top half is written again
+        let (bottom, top) = match mac {
+            Some(mac) => (mac.get_bottom(), Some(mac.get_top().into())),
+            None => (0, None),
+        };
+        macro_rules! write_filter {
+            ($bottom:ident, $top:ident) => {{
+                self.$bottom.set(bottom);
+                if let Some(top) = top {
+                    self.$top.set(top);
+                }
+            }};
+        }
+        match slot {
+            0 => write_filter!(spec_add2_bottom, spec_add2_top),
+            1 => write_filter!(spec_add3_bottom, spec_add3_top),
+            2 => write_filter!(spec_add4_bottom, spec_add4_top),
+            _ => return Err("No such address filter"),
+        }
+        Ok(())
+    }
+
+    pub fn enable_promiscuous_mode(&self) {
+        self.network_config
+            .modify(network_config::COPY_ALL_FRAMES::SET);
+    }
+
+    pub fn disable_promiscuous_mode(&self) {
+        self.network_config
+            .modify(network_config::COPY_ALL_FRAMES::CLEAR);
+    }
+
+    pub fn set_broadcast(&self, receive: bool) {
+        self.network_config
+            .modify(network_config::NO_BROADCAST.val(!receive as u32));
+    }
+
+    // Receive multicast frames to these groups as well, through the hash filter, so a few
+    // other groups sharing a hash bit get through too. An empty list turns the filter off.
+    pub fn set_multicast_filter(&self, groups: &[MacAddress]) {
+        let hash = groups
+            .iter()
+            .fold(0u64, |hash, mac| hash | 1 << multicast_hash_index(mac));
+        self.set_multicast_hash(hash);
+    }
+
+    pub fn accept_all_multicast(&self) {
+        self.set_multicast_hash(u64::MAX);
+    }
+
+    pub fn multicast_hash(&self) -> u64 {
+        (self.hash_top.get() as u64) << 32 | self.hash_bottom.get() as u64
+    }
+
+    fn set_multicast_hash(&self, hash: u64) {
+        self.hash_bottom.set(hash as u32);
+        self.hash_top.set((hash >> 32) as u32);
+        self.network_config
+            .modify(network_config::MULTICAST_HASH_ENABLE.val((hash != 0) as u32));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gem::sim::{driver, GemSim};
+
+    #[test]
+    fn filters_change_while_running() {
+        let all_hosts = MacAddress::new([0x01, 0x00, 0x5E, 0x00, 0x00, 0x01]);
+        let all_nodes = MacAddress::new([0x33, 0x33, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(multicast_hash_index(&all_hosts), 38);
+        assert_eq!(multicast_hash_index(&all_nodes), 44);
+        assert_eq!(multicast_hash_index(&MacAddress::new([0xFF; 6])), 0);
+
+        let mut sim = GemSim::new();
+        let mut driver = driver(&mut sim);
+
+        let dev = driver.device();
+        dev.set_mac_address(MacAddress::new([0x02, 0x11, 0x22, 0x33, 0x44, 0x55]));
+        dev.set_multicast_filter(&[all_hosts, all_nodes]);
+        dev.set_extra_address(1, Some(MacAddress::new([0x02, 0, 0, 0, 0, 9])))
+            .unwrap();
+        assert!(dev.set_extra_address(EXTRA_ADDRESS_FILTERS, None).is_err());
+        sim.step();
+        assert_eq!(
+            driver.mac_address().inner(),
+            [0x02, 0x11, 0x22, 0x33, 0x44, 0x55]
+        );
+        let dev = driver.device();
+        assert_eq!(dev.multicast_hash(), 1 << 38 | 1 << 44);
+        assert!(dev
+            .network_config
+            .is_set(network_config::MULTICAST_HASH_ENABLE));
+        assert_eq!(
+            (dev.spec_add3_bottom.get(), dev.spec_add3_top.get()),
+            (2, 0x0900)
+        );
+
+        // Reception carries on undisturbed
+        sim.inject_rx(&[0x5A; 64]);
+        sim.step();
+        assert_eq!(&driver.receive().unwrap()[..], [0x5A; 64]);
+        driver.device().set_multicast_filter(&[]);
+        assert!(!driver
+            .device()
+            .network_config
+            .is_set(network_config::MULTICAST_HASH_ENABLE));
+    }
+
+    #[test]
+    fn broadcast_and_multicast_filtered() {
+        let mut sim = GemSim::new();
+        let mut driver = driver(&mut sim);
+        let frame = |dest: [u8; 6]| {
+            let mut frame = [0x5A; 64];
+            frame[..6].copy_from_slice(&dest);
+            frame
+        };
+
+        // Broadcast frames follow the setting
+        let broadcast = frame([0xFF; 6]);
+        driver.device().set_broadcast(false);
+        sim.inject_rx(&broadcast);
+        sim.step();
+        assert!(driver.receive().is_none());
+        driver.device().set_broadcast(true);
+        sim.inject_rx(&broadcast);
+        sim.step();
+        assert_eq!(&driver.receive().unwrap()[..], broadcast);
+
+        // Multicast frames only to the groups joined, until all of them are let through
+        let group = [0x01, 0x00, 0x5E, 0x00, 0x00, 0x01];
+        let others = [
+            [0x01, 0x00, 0x5E, 0x7F, 0xFF, 0xFA],
+            [0x33, 0x33, 0x00, 0x00, 0x00, 0xFB],
+            [0x03, 0x12, 0x34, 0x56, 0x78, 0x9A],
+        ];
+        driver
+            .device()
+            .set_multicast_filter(&[MacAddress::new(group)]);
+        for dest in others {
+            assert_ne!(
+                multicast_hash_index(&MacAddress::new(dest)),
+                multicast_hash_index(&MacAddress::new(group))
+            );
+            sim.inject_rx(&frame(dest));
+        }
+        sim.inject_rx(&frame(group));
+        sim.step();
+        assert_eq!(&driver.receive().unwrap()[..], frame(group));
+        assert!(driver.receive().is_none());
+
+        driver.device().accept_all_multicast();
+        for dest in others {
+            sim.inject_rx(&frame(dest));
+        }
+        sim.step();
+        for dest in others {
+            assert_eq!(&driver.receive().unwrap()[..], frame(dest));
+        }
+    }
+}
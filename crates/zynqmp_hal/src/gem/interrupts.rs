@@ -0,0 +1,280 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use tock_registers::fields::FieldValue;
+use tock_registers::interfaces::{Readable, Writeable};
+
+use zynqmp_pac::gem::{int_moderation, int_status};
+
+use super::{Device, Running};
+
+// Set of bits from one of the GEM status registers, with the usual flag set operations
+macro_rules! flag_set {
+    (
+        $(#[$meta:meta])*
+        $name:ident { $($flag:ident = $value:expr,)* }
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+        pub struct $name(u32);
+
+        impl $name {
+            $(pub const $flag: Self = Self($value);)*
+
+            const NAMES: &'static [(&'static str, Self)] = &[$((stringify!($flag), Self::$flag),)*];
+
+            pub const fn empty() -> Self {
+                Self(0)
+            }
+
+            pub const fn all() -> Self {
+                Self(0 $(| $value)*)
+            }
+
+            pub const fn bits(&self) -> u32 {
+                self.0
+            }
+
+            // Unknown and reserved bits are dropped
+            pub const fn from_bits_truncate(bits: u32) -> Self {
+                Self(bits & Self::all().0)
+            }
+
+            pub const fn union(self, other: Self) -> Self {
+                Self(self.0 | other.0)
+            }
+
+            pub const fn is_empty(&self) -> bool {
+                self.0 == 0
+            }
+
+            pub const fn contains(&self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            pub const fn intersects(&self, other: Self) -> bool {
+                self.0 & other.0 != 0
+            }
+
+            pub fn insert(&mut self, other: Self) {
+                self.0 |= other.0;
+            }
+
+            pub fn remove(&mut self, other: Self) {
+                self.0 &= !other.0;
+            }
+
+            pub fn iter_names(&self) -> impl Iterator<Item = (&'static str, Self)> + '_ {
+                Self::NAMES
+                    .iter()
+                    .copied()
+                    .filter(move |(_, flag)| self.contains(*flag))
+            }
+        }
+
+        impl core::ops::BitOr for $name {
+            type Output = Self;
+
+            fn bitor(self, rhs: Self) -> Self {
+                Self(self.0 | rhs.0)
+            }
+        }
+
+        impl core::ops::BitOrAssign for $name {
+            fn bitor_assign(&mut self, rhs: Self) {
+                self.0 |= rhs.0;
+            }
+        }
+
+        impl core::ops::BitAnd for $name {
+            type Output = Self;
+
+            fn bitand(self, rhs: Self) -> Self {
+                Self(self.0 & rhs.0)
+            }
+        }
+
+        impl core::ops::BitAndAssign for $name {
+            fn bitand_assign(&mut self, rhs: Self) {
+                self.0 &= rhs.0;
+            }
+        }
+
+        impl core::ops::Sub for $name {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self {
+                Self(self.0 & !rhs.0)
+            }
+        }
+
+        impl core::ops::Not for $name {
+            type Output = Self;
+
+            fn not(self) -> Self {
+                Self::from_bits_truncate(!self.0)
+            }
+        }
+
+        impl core::fmt::Debug for $name {
+            fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+                write!(f, "{}(", stringify!($name))?;
+                let mut first = true;
+                for (name, _) in self.iter_names() {
+                    if !first {
+                        write!(f, " | ")?;
+                    }
+                    write!(f, "{}", name)?;
+                    first = false;
+                }
+                write!(f, ")")
+            }
+        }
+    };
+}
+
+pub(crate) use flag_set;
+
+flag_set! {
+    // Set of GEM interrupt sources, using the int_status bit layout. int_enable, int_disable
+    // and int_mask share the same layout.
+    GemInterrupts {
+        MANAGEMENT_DONE = int_status::MANAGEMENT_FRAME_SENT::SET.value,
+        RX_COMPLETE = int_status::RECEIVE_COMPLETE::SET.value,
+        RX_USED_BIT_READ = int_status::RX_USED_BIT_READ::SET.value,
+        TX_USED_BIT_READ = int_status::TX_USED_BIT_READ::SET.value,
+        TX_UNDERRUN = int_status::TRANSMIT_UNDER_RUN::SET.value,
+        RETRY_LIMIT_OR_LATE_COLLISION = int_status::RETRY_LIMIT_EXCEEDED_OR_LATE_COLLISION::SET.value,
+        AMBA_ERROR = int_status::AMBA_ERROR::SET.value,
+        TX_COMPLETE = int_status::TRANSMIT_COMPLETE::SET.value,
+        LINK_CHANGE = int_status::LINK_CHANGE::SET.value,
+        RX_OVERRUN = int_status::RECEIVE_OVERRUN::SET.value,
+        RESP_NOT_OK = int_status::RESP_NOT_OK::SET.value,
+        PAUSE_FRAME_RECEIVED = int_status::PAUSE_FRAME_WITH_NON_ZERO_PAUSE_QUANTUM_RECEIVED::SET.value,
+        PAUSE_TIME_ELAPSED = int_status::PAUSE_TIME_ELAPSED::SET.value,
+        PAUSE_FRAME_TRANSMITTED = int_status::PAUSE_FRAME_TRANSMITTED::SET.value,
+        EXTERNAL = int_status::EXTERNAL_INTERRUPT::SET.value,
+        PCS_AUTONEG_COMPLETE = int_status::PCS_AUTO_NEGOTIATION_COMPLETE::SET.value,
+        PCS_LINK_PARTNER_PAGE_RECEIVED = int_status::PCS_LINK_PARTNER_PAGE_RECEIVED::SET.value,
+        PTP_DELAY_REQ_RECEIVED = int_status::PTP_DELAY_REQ_FRAME_RECEIVED::SET.value,
+        PTP_SYNC_RECEIVED = int_status::PTP_SYNC_FRAME_RECEIVED::SET.value,
+        PTP_DELAY_REQ_TRANSMITTED = int_status::PTP_DELAY_REQ_FRAME_TRANSMITTED::SET.value,
+        PTP_SYNC_TRANSMITTED = int_status::PTP_SYNC_FRAME_TRANSMITTED::SET.value,
+        PTP_PDELAY_REQ_RECEIVED = int_status::PTP_PDELAY_REQ_FRAME_RECEIVED::SET.value,
+        PTP_PDELAY_RESP_RECEIVED = int_status::PTP_PDELAY_RESP_FRAME_RECEIVED::SET.value,
+        PTP_PDELAY_REQ_TRANSMITTED = int_status::PTP_PDELAY_REQ_FRAME_TRANSMITTED::SET.value,
+        PTP_PDELAY_RESP_TRANSMITTED = int_status::PTP_PDELAY_RESP_FRAME_TRANSMITTED::SET.value,
+        TSU_SECONDS_INCREMENT = int_status::TSU_SECONDS_REGISTER_INCREMENT::SET.value,
+        RX_LPI_CHANGE = int_status::RECEIVE_LPI_INDICATION_STATUS_BIT_CHANGE::SET.value,
+        WOL = int_status::WOL_INTERRUPT::SET.value,
+        TSU_TIMER_COMPARISON = int_status::TSU_TIMER_COMPARISON_INTERRUPT::SET.value,
+    }
+}
+
+impl GemInterrupts {
+    // Events which leave the RX ring with work to do
+    pub const RX_EVENTS: Self = Self(
+        Self::RX_COMPLETE.0 | Self::RX_USED_BIT_READ.0 | Self::RX_OVERRUN.0 | Self::RESP_NOT_OK.0,
+    );
+
+    // Events which retire TX descriptors
+    pub const TX_EVENTS: Self = Self(
+        Self::TX_COMPLETE.0
+            | Self::TX_UNDERRUN.0
+            | Self::AMBA_ERROR.0
+            | Self::RETRY_LIMIT_OR_LATE_COLLISION.0,
+    );
+
+    // Events after which transmission has to be recovered, see `Driver::recover_tx`
+    pub const TX_ERRORS: Self =
+        Self(Self::TX_UNDERRUN.0 | Self::AMBA_ERROR.0 | Self::RETRY_LIMIT_OR_LATE_COLLISION.0);
+
+    // Events after which reception may need recovering, see `Driver::poll_rx`
+    pub const RX_ERRORS: Self =
+        Self(Self::RX_USED_BIT_READ.0 | Self::RX_OVERRUN.0 | Self::RESP_NOT_OK.0);
+
+    pub const PTP_EVENTS: Self = Self(
+        Self::PTP_DELAY_REQ_RECEIVED.0
+            | Self::PTP_SYNC_RECEIVED.0
+            | Self::PTP_DELAY_REQ_TRANSMITTED.0
+            | Self::PTP_SYNC_TRANSMITTED.0
+            | Self::PTP_PDELAY_REQ_RECEIVED.0
+            | Self::PTP_PDELAY_RESP_RECEIVED.0
+            | Self::PTP_PDELAY_REQ_TRANSMITTED.0
+            | Self::PTP_PDELAY_RESP_TRANSMITTED.0,
+    );
+}
+
+// How long the controller holds RX and TX complete interrupts back after a frame, so a burst
+// of frames raises a single interrupt. In units of 800 ns at 1 Gb/s, which stretch to 8 us at
+// 100 Mb/s and 80 us at 10 Mb/s. Zero interrupts on every frame.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct InterruptCoalescing {
+    pub rx: u8,
+    pub tx: u8,
+}
+
+impl InterruptCoalescing {
+    pub const fn new(rx: u8, tx: u8) -> Self {
+        Self { rx, tx }
+    }
+
+    pub(crate) fn register_value(&self) -> FieldValue<u32, int_moderation::Register> {
+        int_moderation::RX_INT_MODERATION.val(self.rx as u32)
+            + int_moderation::TX_INT_MODERATION.val(self.tx as u32)
+    }
+}
+
+impl<I> Device<Running, I> {
+    pub fn set_interrupt_coalescing(&self, coalescing: InterruptCoalescing) {
+        self.int_moderation.write(coalescing.register_value());
+    }
+
+    pub fn interrupt_coalescing(&self) -> InterruptCoalescing {
+        let reg = self.int_moderation.extract();
+        InterruptCoalescing {
+            rx: reg.read(int_moderation::RX_INT_MODERATION) as u8,
+            tx: reg.read(int_moderation::TX_INT_MODERATION) as u8,
+        }
+    }
+
+    pub fn enable_interrupts(&self, irqs: GemInterrupts) {
+        self.int_enable.set(irqs.bits());
+    }
+
+    pub fn disable_interrupts(&self, irqs: GemInterrupts) {
+        self.int_disable.set(irqs.bits());
+    }
+
+    // Sources which are currently masked, i.e. will not raise the interrupt line
+    pub fn masked_interrupts(&self) -> GemInterrupts {
+        GemInterrupts::from_bits_truncate(self.int_mask.get())
+    }
+
+    pub fn enabled_interrupts(&self) -> GemInterrupts {
+        !self.masked_interrupts()
+    }
+
+    pub fn pending_interrupts(&self) -> GemInterrupts {
+        GemInterrupts::from_bits_truncate(self.int_status.get())
+    }
+
+    pub fn clear_interrupts(&self, irqs: GemInterrupts) {
+        self.int_status.set(irqs.bits());
+    }
+
+    // Pass the pending, enabled interrupt sources to `handler` and acknowledge only the sources
+    // it reports back as handled. Anything else stays pending for a later pass.
+    pub fn handle_interrupts<F>(&self, handler: F) -> GemInterrupts
+    where
+        F: FnOnce(GemInterrupts) -> GemInterrupts,
+    {
+        let pending = self.pending_interrupts() & self.enabled_interrupts();
+        let handled = handler(pending) & pending;
+        self.clear_interrupts(handled);
+        handled
+    }
+}
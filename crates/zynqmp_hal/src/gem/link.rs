@@ -0,0 +1,146 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use eth_phy::{GenPhy, LinkStatus, PhyReadWrite};
+
+use super::driver::Driver;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkEvent {
+    Up(LinkStatus),
+    Down,
+    // Renegotiated without the link being seen down, e.g. a speed change between polls
+    Changed(LinkStatus),
+}
+
+// Follows the PHY link state and keeps the MAC configuration in step with it. Call `poll`
+// periodically, or whenever the PHY or the GEM LINK_CHANGE interrupt fires.
+#[derive(Debug, Default)]
+pub struct LinkSupervisor {
+    current: Option<LinkStatus>,
+}
+
+impl LinkSupervisor {
+    pub const fn new() -> Self {
+        Self { current: None }
+    }
+
+    pub fn status(&self) -> Option<LinkStatus> {
+        self.current
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.current.is_some()
+    }
+
+    // Returns an event when the link state changed since the last poll. On Up and Changed the
+    // driver has already been reprogrammed for the new link.
+    pub fn poll<T: PhyReadWrite, I>(
+        &mut self,
+        phy: &GenPhy<T>,
+        driver: &mut Driver<'_, I>,
+    ) -> Option<LinkEvent> {
+        let event = self.check(phy);
+        if let Some(LinkEvent::Up(link) | LinkEvent::Changed(link)) = &event {
+            driver.apply_link(link);
+        }
+        event
+    }
+
+    // As `poll`, leaving the MAC to be reprogrammed by whoever owns the driver
+    pub fn check<T: PhyReadWrite>(&mut self, phy: &GenPhy<T>) -> Option<LinkEvent> {
+        let status = phy.link_status();
+        if status == self.current {
+            return None;
+        }
+
+        let event = match (self.current, status) {
+            (_, None) => LinkEvent::Down,
+            (None, Some(link)) => LinkEvent::Up(link),
+            (Some(_), Some(link)) => LinkEvent::Changed(link),
+        };
+        self.current = status;
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gem::sim::{driver, GemSim};
+    use eth_phy::sim::{LinkPartner, SimMdioBus, SimPhy};
+    use eth_phy::{Duplex, Speed, Supported};
+    use zynqmp_pac::gem::network_config;
+
+    use tock_registers::interfaces::Readable;
+
+    #[test]
+    fn mac_follows_phy() {
+        let mut sim = GemSim::new();
+        let mut driver = driver(&mut sim);
+
+        let bus = SimMdioBus::new();
+        bus.add_phy(0, SimPhy::new());
+        let supported = Supported {
+            base100_t_half: true,
+            base100_t_full: true,
+            base1000_t_full: true,
+            pause: true,
+            ..Default::default()
+        };
+        let phy = GenPhy::new(0, &bus, supported);
+        // Pause advertised, as autonegotiation set up by `configure_phy` would
+        bus.with_phy(0, |p| p.set_reg(0x04, p.reg(0x04) | 1 << 10));
+        let mut supervisor = LinkSupervisor::new();
+        assert_eq!(supervisor.poll(&phy, &mut driver), None);
+
+        let gigabit = LinkStatus {
+            speed: Speed::S1000,
+            duplex: Duplex::Full,
+            pause: true,
+        };
+        bus.with_phy(0, |p| {
+            p.set_link_partner(Some(LinkPartner::up_to(Speed::S1000, Duplex::Full, true)))
+        });
+        assert_eq!(
+            supervisor.poll(&phy, &mut driver),
+            Some(LinkEvent::Up(gigabit))
+        );
+        assert_eq!(supervisor.poll(&phy, &mut driver), None);
+        let config = driver.device().network_config.extract();
+        assert!(config.is_set(network_config::GIGABIT_MODE_ENABLE));
+        assert!(config.is_set(network_config::FULL_DUPLEX));
+        assert!(config.is_set(network_config::PAUSE_ENABLE));
+
+        // A partner renegotiating between polls is a change, not a drop
+        bus.with_phy(0, |p| {
+            p.set_link_partner(None);
+            p.set_link_partner(Some(LinkPartner::up_to(Speed::S100, Duplex::Half, false)))
+        });
+        let fast = LinkStatus {
+            speed: Speed::S100,
+            duplex: Duplex::Half,
+            pause: false,
+        };
+        assert_eq!(
+            supervisor.poll(&phy, &mut driver),
+            Some(LinkEvent::Changed(fast))
+        );
+        let config = driver.device().network_config.extract();
+        assert!(!config.is_set(network_config::GIGABIT_MODE_ENABLE));
+        assert!(config.is_set(network_config::SPEED));
+        assert!(!config.is_set(network_config::FULL_DUPLEX));
+
+        bus.with_phy(0, |p| p.set_link_partner(None));
+        assert_eq!(supervisor.check(&phy), Some(LinkEvent::Down));
+        assert!(!supervisor.is_up());
+
+        // Frames still flow once the MAC was reprogrammed
+        sim.inject_rx(&[0x5A; 64]);
+        sim.step();
+        assert_eq!(&driver.receive().unwrap()[..], [0x5A; 64]);
+    }
+}
@@ -0,0 +1,254 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+// Carves descriptor arrays and packet buffers out of one region of DMA capable memory. Nothing
+// is ever freed, the pool is meant to be set up once at boot.
+
+#[cfg(any(test, feature = "sim"))]
+extern crate std;
+
+use core::mem::{align_of, size_of};
+
+use zynqmp_pac::gem::tx_desc_status;
+
+use super::ring::{RxDescriptor, RxRing, TxDescriptor, TxRing, RX_BUF_UNIT};
+
+// Everything is handed out on cache line boundaries and padded to whole lines, so cache
+// maintenance on one allocation never touches another
+pub const DMA_ALIGN: usize = 64;
+
+// The bounds of a region the controller can use, which has to lie within one 4 GiB window as
+// it takes the upper half of every address from the queue base address registers
+fn check_region(start: usize, end: usize) -> Result<(), &'static str> {
+    if end < start {
+        return Err("DMA region ends before it starts");
+    }
+    if end == start {
+        return Err("Empty DMA region");
+    }
+    if (start as u64) >> 32 != ((end - 1) as u64) >> 32 {
+        return Err("DMA region crosses a 4 GiB boundary");
+    }
+    Ok(())
+}
+
+pub struct DmaPool {
+    next: usize,
+    end: usize,
+}
+
+impl DmaPool {
+    // The region has to lie within one 4 GiB window, see `check_region`
+    pub fn new(region: &'static mut [u8]) -> Result<Self, &'static str> {
+        let start = region.as_mut_ptr() as usize;
+        let end = start + region.len();
+        check_region(start, end)?;
+        Ok(Self { next: start, end })
+    }
+
+    // Pool over the memory between two linker symbols, e.g. the bounds of a section placed in
+    // DMA capable memory.
+    /// # Safety
+    ///
+    /// Nothing else may use the memory from `start` up to `end` for the rest of the program.
+    pub unsafe fn from_raw(start: *mut u8, end: *mut u8) -> Result<Self, &'static str> {
+        let (start, end) = (start as usize, end as usize);
+        check_region(start, end)?;
+        Ok(Self { next: start, end })
+    }
+
+    // Pool in leaked heap memory, for host tests
+    #[cfg(any(test, feature = "sim"))]
+    pub fn on_heap(size: usize) -> Self {
+        assert!(size > 0);
+        let layout = std::alloc::Layout::from_size_align(size, DMA_ALIGN).unwrap();
+        // Safety: the layout has a non-zero size, and the memory is leaked
+        let region = unsafe {
+            let ptr = std::alloc::alloc_zeroed(layout);
+            assert!(!ptr.is_null());
+            core::slice::from_raw_parts_mut(ptr, size)
+        };
+        Self::new(region).unwrap()
+    }
+
+    // Bytes left, before alignment
+    pub fn remaining(&self) -> usize {
+        self.end - self.next
+    }
+
+    fn alloc(&mut self, len: usize) -> Result<*mut u8, &'static str> {
+        let start = self.next.next_multiple_of(DMA_ALIGN);
+        let len = len
+            .checked_next_multiple_of(DMA_ALIGN)
+            .unwrap_or(usize::MAX);
+        debug_assert!(len > 0);
+        if start > self.end || self.end - start < len {
+            return Err("DMA pool exhausted");
+        }
+        self.next = start + len;
+        Ok(start as *mut u8)
+    }
+
+    // `count` descriptors, initialised
+    fn descriptors<T>(
+        &mut self,
+        count: usize,
+        new: fn() -> T,
+    ) -> Result<&'static mut [T], &'static str> {
+        debug_assert!(DMA_ALIGN.is_multiple_of(align_of::<T>()));
+        if count == 0 {
+            return Err("No descriptors asked for");
+        }
+        let len = count
+            .checked_mul(size_of::<T>())
+            .ok_or("Too many descriptors")?;
+        let ptr = self.alloc(len)? as *mut T;
+        for i in 0..count {
+            // Safety: the allocation is aligned and large enough for `count` descriptors
+            unsafe { ptr.add(i).write(new()) };
+        }
+        // Safety: initialised above, and never handed out again
+        Ok(unsafe { core::slice::from_raw_parts_mut(ptr, count) })
+    }
+
+    pub fn rx_descriptors(
+        &mut self,
+        count: usize,
+    ) -> Result<&'static mut [RxDescriptor], &'static str> {
+        self.descriptors(count, RxDescriptor::new)
+    }
+
+    pub fn tx_descriptors(
+        &mut self,
+        count: usize,
+    ) -> Result<&'static mut [TxDescriptor], &'static str> {
+        self.descriptors(count, TxDescriptor::new)
+    }
+
+    // `count` packet buffers of `buf_size` bytes each, back to back
+    pub fn buffers(
+        &mut self,
+        count: usize,
+        buf_size: usize,
+    ) -> Result<&'static mut [u8], &'static str> {
+        let len = count.checked_mul(buf_size).ok_or("Too many buffers")?;
+        if len == 0 {
+            return Err("No buffers asked for");
+        }
+        let ptr = self.alloc(len)?;
+        // Safety: never handed out again. Zeroed first, as the memory may never have been
+        // written.
+        unsafe {
+            ptr.write_bytes(0, len);
+            Ok(core::slice::from_raw_parts_mut(ptr, len))
+        }
+    }
+
+    // RX ring of `count` descriptors with a buffer each, `buf_size` a multiple of 64 bytes
+    pub fn rx_ring(
+        &mut self,
+        count: usize,
+        buf_size: usize,
+    ) -> Result<RxRing<'static>, &'static str> {
+        if count == 0
+            || buf_size == 0
+            || !buf_size.is_multiple_of(RX_BUF_UNIT)
+            || buf_size > 0xFF * RX_BUF_UNIT
+        {
+            return Err("Invalid RX ring geometry");
+        }
+        let descs = self.rx_descriptors(count)?;
+        let buffers = self.buffers(count, buf_size)?;
+        Ok(RxRing::new(descs, buffers, buf_size))
+    }
+
+    // TX ring of `count` descriptors with a buffer each
+    pub fn tx_ring(
+        &mut self,
+        count: usize,
+        buf_size: usize,
+    ) -> Result<TxRing<'static>, &'static str> {
+        if count == 0 || buf_size == 0 || buf_size > tx_desc_status::LENGTH.mask as usize {
+            return Err("Invalid TX ring geometry");
+        }
+        let descs = self.tx_descriptors(count)?;
+        let buffers = self.buffers(count, buf_size)?;
+        Ok(TxRing::new(descs, buffers, buf_size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gem::sim::{driver_with, GemSim};
+    use crate::gem::GemConfig;
+
+    #[test]
+    fn rings_from_pool_carry_frames() {
+        let mut pool = DmaPool::on_heap(64 * 1024);
+        let rx = pool.rx_ring(4, 1536).unwrap();
+        let tx = pool.tx_ring(4, 1500).unwrap();
+        assert!(
+            rx.base_addr().is_multiple_of(DMA_ALIGN) && tx.base_addr().is_multiple_of(DMA_ALIGN)
+        );
+        assert_eq!(tx.base_addr() - rx.base_addr(), 64 + 4 * 1536);
+        // The 1500 byte TX buffers are padded to whole cache lines
+        assert_eq!(pool.remaining(), 64 * 1024 - (64 + 6144) - (64 + 6016));
+
+        let mut sim = GemSim::new();
+        let mut driver = driver_with(&mut sim, GemConfig::new(), rx, tx);
+        sim.inject_rx(&[0x5A; 64]);
+        sim.step();
+        assert_eq!(&driver.receive().unwrap()[..], [0x5A; 64]);
+        driver.transmit(64, |buf| buf.fill(0xA5)).unwrap();
+        sim.step();
+        assert_eq!(sim.take_tx().unwrap().data, [0xA5; 64]);
+
+        // Refused outright rather than handing out memory the rings cannot use
+        assert!(pool.rx_ring(4, 1500).is_err());
+        assert!(pool.buffers(1, 64 * 1024).is_err());
+        assert!(pool.buffers(usize::MAX / 2, 4).is_err());
+        assert!(pool.rx_descriptors(usize::MAX / 4).is_err());
+        assert!(pool.buffers(1, usize::MAX).is_err());
+        assert!(check_region(0xFFFF_F000, 0x1_0000_1000).is_err());
+        assert!(check_region(0x1000, 0x1000).is_err());
+        assert!(check_region(0x2000, 0x1000).is_err());
+        assert!(check_region(0x1000, 0x1_0000_0000).is_ok());
+    }
+
+    #[test]
+    fn pool_errors_reported() {
+        // Safety: the bounds are refused before anything is written to them
+        let backwards = unsafe { DmaPool::from_raw(0x2000 as *mut u8, 0x1000 as *mut u8) };
+        assert_eq!(backwards.err(), Some("DMA region ends before it starts"));
+        let empty = unsafe { DmaPool::from_raw(0x1000 as *mut u8, 0x1000 as *mut u8) };
+        assert_eq!(empty.err(), Some("Empty DMA region"));
+
+        let mut pool = DmaPool::on_heap(1024);
+        assert_eq!(
+            pool.tx_descriptors(usize::MAX).err(),
+            Some("Too many descriptors")
+        );
+        assert_eq!(
+            pool.rx_descriptors(0).err(),
+            Some("No descriptors asked for")
+        );
+        assert_eq!(
+            pool.buffers(usize::MAX / 2, 3).err(),
+            Some("Too many buffers")
+        );
+        assert_eq!(pool.buffers(4, 0).err(), Some("No buffers asked for"));
+        // None of which used up any of the pool
+        assert_eq!(pool.remaining(), 1024);
+
+        // Allocations are whole cache lines, so 600 bytes leave too little for another 400
+        assert!(pool.buffers(1, 600).is_ok());
+        assert_eq!(pool.buffers(1, 400).err(), Some("DMA pool exhausted"));
+        assert!(pool.buffers(1, 384).is_ok());
+        assert_eq!(pool.remaining(), 0);
+        assert_eq!(pool.rx_descriptors(1).err(), Some("DMA pool exhausted"));
+    }
+}
@@ -0,0 +1,762 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use core::marker::PhantomData;
+
+use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
+use tock_registers::registers::InMemoryRegister;
+use tock_registers::LocalRegisterCopy;
+
+use zynqmp_pac::gem::{rx_desc_addr, rx_desc_status, tx_desc_status};
+
+use super::cache::{CacheOps, Coherent};
+use super::translate::DmaTranslation;
+
+// RX buffer sizes are programmed in dma_config in units of 64 bytes
+pub const RX_BUF_UNIT: usize = 64;
+// Completions of frames retired without a caller to report them to, kept for the next
+// `TxRing::reclaim_with`
+pub const TX_COMPLETION_QUEUE: usize = 32;
+
+#[repr(C, align(8))]
+pub struct RxDescriptor {
+    addr: InMemoryRegister<u32, rx_desc_addr::Register>,
+    status: InMemoryRegister<u32, rx_desc_status::Register>,
+}
+
+#[repr(C, align(8))]
+pub struct TxDescriptor {
+    addr: InMemoryRegister<u32>,
+    status: InMemoryRegister<u32, tx_desc_status::Register>,
+}
+
+impl RxDescriptor {
+    pub const fn new() -> Self {
+        Self {
+            addr: InMemoryRegister::new(0),
+            status: InMemoryRegister::new(0),
+        }
+    }
+}
+
+impl Default for RxDescriptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TxDescriptor {
+    pub const fn new() -> Self {
+        Self {
+            addr: InMemoryRegister::new(0),
+            status: InMemoryRegister::new(0),
+        }
+    }
+}
+
+impl Default for TxDescriptor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Make descriptor updates visible to the DMA engine before handing them over, and make sure
+// descriptor reads are not reordered ahead of the ownership check.
+#[inline(always)]
+pub(crate) fn dma_barrier() {
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        core::arch::asm!("dsb sy", options(nostack, preserves_flags))
+    };
+    #[cfg(not(target_arch = "aarch64"))]
+    core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+}
+
+// Whether the controller reaches `len` bytes at `addr` through descriptors at bus address
+// `descs`. Descriptors hold the low 32 bits of buffer addresses, the upper 32 bits come from
+// the queue base, so the bytes have to lie contiguously in the same 4 GiB window.
+fn reaches(translation: &DmaTranslation, descs: usize, addr: usize, len: usize) -> bool {
+    if len == 0 {
+        return false;
+    }
+    let window = descs as u64 >> 32;
+    let start = translation.bus_addr(addr);
+    let end = translation.bus_addr(addr + len - 1);
+    end.wrapping_sub(start) == len as u64 - 1 && start >> 32 == window && end >> 32 == window
+}
+
+pub struct RxRing<'a> {
+    descs: &'a mut [RxDescriptor],
+    buffers: *mut u8,
+    buf_size: usize,
+    // network_config RECEIVE_BUFFER_OFFSET, frame data starts this far into each buffer
+    offset: usize,
+    next: usize,
+    translation: DmaTranslation<'a>,
+    cache: &'a dyn CacheOps,
+    phantom: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> RxRing<'a> {
+    // The memory has to outlive the controller's use of it, which a driver forgotten or a
+    // device leaked never ends
+    pub fn new(
+        descs: &'static mut [RxDescriptor],
+        buffers: &'static mut [u8],
+        buf_size: usize,
+    ) -> Self {
+        assert!(!descs.is_empty());
+        assert!(
+            buf_size > 0 && buf_size.is_multiple_of(RX_BUF_UNIT) && buf_size <= 0xFF * RX_BUF_UNIT
+        );
+        assert!(buffers.len() >= descs.len() * buf_size);
+        assert!((buffers.as_ptr() as usize).is_multiple_of(8));
+
+        let mut ring = Self {
+            descs,
+            buffers: buffers.as_mut_ptr(),
+            buf_size,
+            offset: 0,
+            next: 0,
+            translation: DmaTranslation::Identity,
+            cache: &Coherent,
+            phantom: PhantomData,
+        };
+        assert!(
+            ring.buffers_in_window(),
+            "RX buffers out of reach of the ring"
+        );
+        ring.reset();
+        ring
+    }
+
+    // Hand the controller bus addresses rather than CPU addresses
+    pub fn with_translation(
+        mut self,
+        translation: DmaTranslation<'a>,
+    ) -> Result<Self, &'static str> {
+        if !translation
+            .bus_addr(self.buffers as usize)
+            .is_multiple_of(8)
+        {
+            return Err("Misaligned RX buffers");
+        }
+        self.translation = translation;
+        if !self.buffers_in_window() {
+            return Err("RX buffers out of reach of the ring");
+        }
+        self.reset();
+        Ok(self)
+    }
+
+    // Maintain the caches for buffers the controller does not access coherently
+    /// # Safety
+    ///
+    /// The descriptors must be in memory the controller accesses coherently, or which is mapped
+    /// uncached. Several share a cache line, so they cannot be maintained one at a time.
+    pub unsafe fn with_cache(mut self, cache: &'a dyn CacheOps) -> Self {
+        self.cache = cache;
+        self.reset();
+        self
+    }
+
+    pub fn reset(&mut self) {
+        let last = self.descs.len() - 1;
+        for i in 0..self.descs.len() {
+            let addr = self.buf_addr(i);
+            let desc = &self.descs[i];
+            desc.status.set(0);
+            desc.addr.write(
+                rx_desc_addr::ADDRESS.val(addr >> 2)
+                    + rx_desc_addr::WRAP.val((i == last) as u32)
+                    + rx_desc_addr::OWNERSHIP::Hardware,
+            );
+        }
+        self.next = 0;
+        dma_barrier();
+        // Nothing dirty may be left to be written back over incoming frames
+        self.cache
+            .clean_invalidate(self.buffers as usize, self.descs.len() * self.buf_size);
+    }
+
+    pub fn len(&self) -> usize {
+        self.descs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.descs.is_empty()
+    }
+
+    pub fn buf_size(&self) -> usize {
+        self.buf_size
+    }
+
+    pub fn base_addr(&self) -> usize {
+        self.descs.as_ptr() as usize
+    }
+
+    // Where the controller finds the descriptors, for the queue pointer
+    pub fn dma_base_addr(&self) -> usize {
+        self.translation.desc_addr(self.base_addr()) as usize
+    }
+
+    pub fn translation(&self) -> DmaTranslation<'a> {
+        self.translation
+    }
+
+    fn buf_addr(&self, index: usize) -> u32 {
+        let addr = self.buffers.wrapping_add(index * self.buf_size) as usize;
+        self.translation.bus_addr(addr) as u32
+    }
+
+    fn buffers_in_window(&self) -> bool {
+        (0..self.descs.len()).all(|i| {
+            let addr = self.buffers.wrapping_add(i * self.buf_size) as usize;
+            reaches(&self.translation, self.dma_base_addr(), addr, self.buf_size)
+        })
+    }
+
+    // Length of the next complete frame, if the controller has handed one back. Frames which
+    // did not fit in a single buffer are dropped.
+    pub(crate) fn pending(&mut self) -> Option<usize> {
+        loop {
+            match self.pending_chain()? {
+                (1, len) => return Some(len),
+                (descriptors, _) => self.release_n(descriptors),
+            }
+        }
+    }
+
+    // Descriptor count and length of the next complete frame, however many buffers it spans.
+    // What is left of frames the controller abandoned part way is dropped.
+    pub(crate) fn pending_chain(&mut self) -> Option<(usize, usize)> {
+        'frame: loop {
+            if self.hardware_owns(self.next) {
+                return None;
+            }
+            dma_barrier();
+            if !self.descs[self.next]
+                .status
+                .is_set(rx_desc_status::START_OF_FRAME)
+            {
+                self.release();
+                continue;
+            }
+
+            let mut index = self.next;
+            let mut descriptors = 1;
+            loop {
+                let status = self.descs[index].status.extract();
+                if status.is_set(rx_desc_status::END_OF_FRAME) {
+                    for i in 0..descriptors {
+                        self.fetch_buffer((self.next + i) % self.descs.len());
+                    }
+                    return Some((descriptors, status.read(rx_desc_status::LENGTH) as usize));
+                }
+                if descriptors == self.descs.len() {
+                    self.release_n(descriptors);
+                    continue 'frame;
+                }
+
+                index = (index + 1) % self.descs.len();
+                if self.hardware_owns(index) {
+                    // Rest of the frame still to come
+                    return None;
+                }
+                dma_barrier();
+                if self.descs[index]
+                    .status
+                    .is_set(rx_desc_status::START_OF_FRAME)
+                {
+                    // A new frame started before this one ended
+                    self.release_n(descriptors);
+                    continue 'frame;
+                }
+                descriptors += 1;
+            }
+        }
+    }
+
+    // Hand back descriptors still holding what is left of abandoned frames. Returns whether the
+    // controller has any descriptor to write to.
+    pub(crate) fn refill(&mut self) -> bool {
+        self.pending_chain();
+        (0..self.descs.len()).any(|i| self.hardware_owns(i))
+    }
+
+    // Whether the controller has handed back the descriptor at `next`, without looking any
+    // further
+    pub(crate) fn has_pending(&self) -> bool {
+        !self.hardware_owns(self.next)
+    }
+
+    fn hardware_owns(&self, index: usize) -> bool {
+        self.descs[index]
+            .addr
+            .matches_all(rx_desc_addr::OWNERSHIP::Hardware)
+    }
+
+    // Drop stale cached data of the buffer at `index`, which the controller has written
+    fn fetch_buffer(&self, index: usize) {
+        self.cache
+            .clean_invalidate(self.buffers as usize + index * self.buf_size, self.buf_size);
+    }
+
+    // Status word of the descriptor at `next`
+    pub(crate) fn status(&self) -> LocalRegisterCopy<u32, rx_desc_status::Register> {
+        self.status_at(0)
+    }
+
+    // Status word of the descriptor `i` places after `next`
+    pub(crate) fn status_at(&self, i: usize) -> LocalRegisterCopy<u32, rx_desc_status::Register> {
+        self.descs[(self.next + i) % self.descs.len()]
+            .status
+            .extract()
+    }
+
+    pub(crate) fn set_offset(&mut self, offset: usize) {
+        self.offset = offset;
+    }
+
+    pub(crate) fn buffer(&mut self, len: usize) -> &mut [u8] {
+        let len = len.min(self.buf_size - self.offset);
+        let start = self.next * self.buf_size + self.offset;
+        // Safety: software owns the descriptor at `next`, so the controller will not write to
+        // its buffer until it is released.
+        unsafe { core::slice::from_raw_parts_mut(self.buffers.add(start), len) }
+    }
+
+    pub(crate) fn frame(&self, len: usize) -> &[u8] {
+        let len = len.min(self.buf_size - self.offset);
+        let start = self.next * self.buf_size + self.offset;
+        // Safety: as for `buffer`
+        unsafe { core::slice::from_raw_parts(self.buffers.add(start), len) }
+    }
+
+    // Part `i` of a `len` byte frame starting at `next`. Only the first buffer of a frame has
+    // the offset in front of it.
+    pub(crate) fn segment(&self, i: usize, len: usize) -> &[u8] {
+        let first = self.buf_size - self.offset;
+        let (start, before) = match i {
+            0 => (self.offset, 0),
+            _ => (0, first + (i - 1) * self.buf_size),
+        };
+        let len = (self.buf_size - start).min(len.saturating_sub(before));
+        let index = (self.next + i) % self.descs.len();
+        // Safety: as for `buffer`, the whole frame is owned by software
+        unsafe { core::slice::from_raw_parts(self.buffers.add(index * self.buf_size + start), len) }
+    }
+
+    pub(crate) fn release_n(&mut self, count: usize) {
+        for _ in 0..count {
+            self.release();
+        }
+    }
+
+    // Hand the buffer at `next` back to the controller
+    pub(crate) fn release(&mut self) {
+        // Whatever the CPU wrote to the buffer must not be written back over the next frame
+        self.fetch_buffer(self.next);
+        let desc = &self.descs[self.next];
+        desc.status.set(0);
+        dma_barrier();
+        desc.addr.modify(rx_desc_addr::OWNERSHIP::Hardware);
+        self.next = (self.next + 1) % self.descs.len();
+    }
+}
+
+pub struct TxRing<'a> {
+    descs: &'a mut [TxDescriptor],
+    buffers: *mut u8,
+    buf_size: usize,
+    head: usize,
+    tail: usize,
+    in_flight: usize,
+    // Running frame counts, used to tell when a caller provided buffer is free again
+    submitted: u64,
+    retired: u64,
+    // Oldest first from `done_head`, the oldest are dropped once full
+    done: [TxCompletion; TX_COMPLETION_QUEUE],
+    done_head: usize,
+    done_len: usize,
+    done_dropped: u64,
+    translation: DmaTranslation<'a>,
+    cache: &'a dyn CacheOps,
+    phantom: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> TxRing<'a> {
+    // `'static` memory, as for `RxRing::new`
+    pub fn new(
+        descs: &'static mut [TxDescriptor],
+        buffers: &'static mut [u8],
+        buf_size: usize,
+    ) -> Self {
+        assert!(!descs.is_empty());
+        assert!(buf_size > 0 && buf_size <= tx_desc_status::LENGTH.mask as usize);
+        assert!(buffers.len() >= descs.len() * buf_size);
+
+        let mut ring = Self {
+            descs,
+            buffers: buffers.as_mut_ptr(),
+            buf_size,
+            head: 0,
+            tail: 0,
+            in_flight: 0,
+            submitted: 0,
+            retired: 0,
+            done: [TxCompletion::EMPTY; TX_COMPLETION_QUEUE],
+            done_head: 0,
+            done_len: 0,
+            done_dropped: 0,
+            translation: DmaTranslation::Identity,
+            cache: &Coherent,
+            phantom: PhantomData,
+        };
+        assert!(
+            ring.buffers_in_window(),
+            "TX buffers out of reach of the ring"
+        );
+        ring.reset();
+        ring
+    }
+
+    // Hand the controller bus addresses rather than CPU addresses
+    pub fn with_translation(
+        mut self,
+        translation: DmaTranslation<'a>,
+    ) -> Result<Self, &'static str> {
+        self.translation = translation;
+        if !self.buffers_in_window() {
+            return Err("TX buffers out of reach of the ring");
+        }
+        self.reset();
+        Ok(self)
+    }
+
+    // Maintain the caches for buffers the controller does not access coherently
+    /// # Safety
+    ///
+    /// The descriptors must be in memory the controller accesses coherently, or which is mapped
+    /// uncached. Several share a cache line, so they cannot be maintained one at a time.
+    pub unsafe fn with_cache(mut self, cache: &'a dyn CacheOps) -> Self {
+        self.cache = cache;
+        self.reset();
+        self
+    }
+
+    pub fn reset(&mut self) {
+        let last = self.descs.len() - 1;
+        for i in 0..self.descs.len() {
+            let addr = self.buf_addr(i);
+            let desc = &self.descs[i];
+            desc.addr.set(addr);
+            desc.status
+                .write(tx_desc_status::USED::SET + tx_desc_status::WRAP.val((i == last) as u32));
+        }
+        self.head = 0;
+        self.tail = 0;
+        self.in_flight = 0;
+        // The controller no longer reads anything that was queued
+        self.retired = self.submitted;
+        dma_barrier();
+    }
+
+    pub fn len(&self) -> usize {
+        self.descs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.descs.is_empty()
+    }
+
+    pub fn buf_size(&self) -> usize {
+        self.buf_size
+    }
+
+    pub fn base_addr(&self) -> usize {
+        self.descs.as_ptr() as usize
+    }
+
+    // Where the controller finds the descriptors, for the queue pointer
+    pub fn dma_base_addr(&self) -> usize {
+        self.translation.desc_addr(self.base_addr()) as usize
+    }
+
+    pub fn translation(&self) -> DmaTranslation<'a> {
+        self.translation
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+
+    fn buf_addr(&self, index: usize) -> u32 {
+        let addr = self.buffers.wrapping_add(index * self.buf_size) as usize;
+        self.translation.bus_addr(addr) as u32
+    }
+
+    fn buffers_in_window(&self) -> bool {
+        (0..self.descs.len()).all(|i| {
+            let addr = self.buffers.wrapping_add(i * self.buf_size) as usize;
+            reaches(&self.translation, self.dma_base_addr(), addr, self.buf_size)
+        })
+    }
+
+    fn is_last(&self, index: usize) -> bool {
+        index == self.descs.len() - 1
+    }
+
+    // Walk forward from the oldest frame and retire every frame the controller has finished
+    // with, queueing the completions. Returns the number of frames retired.
+    pub(crate) fn reclaim(&mut self) -> usize {
+        let mut count = 0;
+        while let Some(completion) = self.retire_next() {
+            self.queue(completion);
+            count += 1;
+        }
+        dma_barrier();
+        count
+    }
+
+    // As `reclaim`, reporting the queued completions and then those of the frames retired now,
+    // in submission order. Returns the number of completions reported.
+    pub(crate) fn reclaim_with<F: FnMut(TxCompletion)>(&mut self, mut f: F) -> usize {
+        let mut count = 0;
+        while self.done_len > 0 {
+            f(self.done[self.done_head]);
+            self.done_head = (self.done_head + 1) % TX_COMPLETION_QUEUE;
+            self.done_len -= 1;
+            count += 1;
+        }
+        while let Some(completion) = self.retire_next() {
+            f(completion);
+            count += 1;
+        }
+        dma_barrier();
+        count
+    }
+
+    // Retire the oldest frame, if the controller has finished with it
+    fn retire_next(&mut self) -> Option<TxCompletion> {
+        if self.in_flight == 0 {
+            return None;
+        }
+        // The controller only marks the first descriptor of a frame, and reports errors there
+        // too
+        let status = self.descs[self.tail].status.extract();
+        if !status.is_set(tx_desc_status::USED) {
+            return None;
+        }
+        dma_barrier();
+        Some(self.retire_frame(TxError::from_status(status)))
+    }
+
+    fn queue(&mut self, completion: TxCompletion) {
+        if self.done_len == TX_COMPLETION_QUEUE {
+            self.done_head = (self.done_head + 1) % TX_COMPLETION_QUEUE;
+            self.done_len -= 1;
+            self.done_dropped += 1;
+        }
+        self.done[(self.done_head + self.done_len) % TX_COMPLETION_QUEUE] = completion;
+        self.done_len += 1;
+    }
+
+    // Completions dropped from a full queue, for callers not interested in them
+    pub(crate) fn completions_dropped(&self) -> u64 {
+        self.done_dropped
+    }
+
+    // Give up on every frame still queued, with the controller halted, queueing the
+    // completions. Returns the number of frames dropped.
+    pub(crate) fn abort(&mut self) -> usize {
+        let mut count = 0;
+        while self.in_flight > 0 {
+            let completion = self.retire_frame(Err(TxError::Aborted));
+            self.queue(completion);
+            count += 1;
+        }
+        count
+    }
+
+    // As `abort`, reporting the completions to `f`
+    pub(crate) fn abort_with<F: FnMut(TxCompletion)>(&mut self, mut f: F) -> usize {
+        let mut count = 0;
+        while self.in_flight > 0 {
+            f(self.retire_frame(Err(TxError::Aborted)));
+            count += 1;
+        }
+        count
+    }
+
+    // Retire the frame at `tail`, however many descriptors it spans
+    fn retire_frame(&mut self, result: Result<(), TxError>) -> TxCompletion {
+        let mut index = self.tail;
+        let mut descriptors = 1;
+        while !self.descs[index].status.is_set(tx_desc_status::LAST_BUFFER)
+            && descriptors < self.in_flight
+        {
+            index = (index + 1) % self.descs.len();
+            descriptors += 1;
+            // Park the rest of the frame, so the controller stops here once it wraps
+            self.descs[index].status.modify(tx_desc_status::USED::SET);
+        }
+
+        let completion = TxCompletion {
+            id: TxFrameId(self.retired),
+            descriptors,
+            result,
+        };
+        self.tail = (index + 1) % self.descs.len();
+        self.in_flight -= descriptors;
+        self.retired += 1;
+        completion
+    }
+
+    // Whether the frame with sequence number `seq` has left the ring
+    pub(crate) fn is_retired(&self, seq: u64) -> bool {
+        seq < self.retired
+    }
+
+    // Whether `count` descriptors are free, retiring sent frames if need be
+    pub(crate) fn has_room(&mut self, count: usize) -> bool {
+        if self.descs.len() - self.in_flight < count {
+            self.reclaim();
+        }
+        self.descs.len() - self.in_flight >= count
+    }
+
+    pub(crate) fn buffer(&mut self, len: usize) -> &mut [u8] {
+        assert!(len <= self.buf_size);
+        // Safety: the descriptor at `head` is not in flight, so the controller will not read
+        // its buffer until it is submitted.
+        unsafe { core::slice::from_raw_parts_mut(self.buffers.add(self.head * self.buf_size), len) }
+    }
+
+    // Hand the buffer at `head` to the controller as a single buffer frame
+    pub(crate) fn submit(&mut self, len: usize) {
+        self.cache
+            .clean(self.buffers as usize + self.head * self.buf_size, len);
+        self.submit_addr(self.buf_addr(self.head), len);
+    }
+
+    // Whether the controller can fetch `len` bytes at `addr` in one descriptor
+    pub(crate) fn can_reach(&self, addr: usize, len: usize) -> bool {
+        len <= tx_desc_status::LENGTH.mask as usize
+            && reaches(&self.translation, self.dma_base_addr(), addr, len)
+    }
+
+    // Queue a frame from memory outside the ring, which must stay untouched until the
+    // returned sequence number is retired
+    pub(crate) fn submit_external(&mut self, addr: usize, len: usize) -> u64 {
+        let seq = self.submitted;
+        self.cache.clean(addr, len);
+        self.submit_addr(self.translation.bus_addr(addr) as u32, len);
+        seq
+    }
+
+    fn submit_addr(&mut self, addr: u32, len: usize) {
+        dma_barrier();
+        self.write_desc(self.head, addr, len, true, false);
+        dma_barrier();
+        self.advance(1)
+    }
+
+    // Queue one frame gathered from several buffers, one descriptor each. The buffers must
+    // stay untouched until the returned sequence number is retired.
+    pub(crate) fn submit_gather(&mut self, parts: &[&[u8]]) -> u64 {
+        let seq = self.submitted;
+        let first = self.head;
+        let last = parts.len() - 1;
+        // Fill in back to front and release the first descriptor last, so the controller
+        // never sees half a frame
+        for (i, part) in parts.iter().enumerate().rev() {
+            let index = (first + i) % self.descs.len();
+            self.cache.clean(part.as_ptr() as usize, part.len());
+            self.write_desc(
+                index,
+                self.translation.bus_addr(part.as_ptr() as usize) as u32,
+                part.len(),
+                i == last,
+                i == 0,
+            );
+        }
+        dma_barrier();
+        self.descs[first].status.modify(tx_desc_status::USED::CLEAR);
+        dma_barrier();
+        self.advance(parts.len());
+        seq
+    }
+
+    fn write_desc(&self, index: usize, addr: u32, len: usize, last: bool, used: bool) {
+        let desc = &self.descs[index];
+        desc.addr.set(addr);
+        desc.status.write(
+            tx_desc_status::LENGTH.val(len as u32)
+                + tx_desc_status::LAST_BUFFER.val(last as u32)
+                + tx_desc_status::WRAP.val(self.is_last(index) as u32)
+                + tx_desc_status::USED.val(used as u32),
+        );
+    }
+
+    fn advance(&mut self, descriptors: usize) {
+        self.head = (self.head + descriptors) % self.descs.len();
+        self.in_flight += descriptors;
+        self.submitted += 1;
+    }
+}
+
+// Identifies a queued frame in completions. Assigned in submission order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TxFrameId(pub(crate) u64);
+
+// Why the controller gave up on a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxError {
+    RetryLimitExceeded,
+    LateCollision,
+    // The DMA hit a bus error fetching the frame
+    BusError,
+    // Checksum generation failed, with the code from the descriptor
+    Checksum(u8),
+    // Still queued when transmission was recovered from an error
+    Aborted,
+}
+
+impl TxError {
+    fn from_status(
+        status: LocalRegisterCopy<u32, tx_desc_status::Register>,
+    ) -> Result<(), TxError> {
+        if status.is_set(tx_desc_status::AMBA_ERROR) {
+            Err(TxError::BusError)
+        } else if status.is_set(tx_desc_status::RETRY_LIMIT_EXCEEDED) {
+            Err(TxError::RetryLimitExceeded)
+        } else if status.is_set(tx_desc_status::LATE_COLLISION) {
+            Err(TxError::LateCollision)
+        } else {
+            match status.read(tx_desc_status::CHECKSUM_ERROR) {
+                0 => Ok(()),
+                code => Err(TxError::Checksum(code as u8)),
+            }
+        }
+    }
+}
+
+// Outcome of one frame, however many descriptors it took
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxCompletion {
+    pub id: TxFrameId,
+    pub descriptors: usize,
+    pub result: Result<(), TxError>,
+}
+
+impl TxCompletion {
+    const EMPTY: Self = Self {
+        id: TxFrameId(0),
+        descriptors: 0,
+        result: Ok(()),
+    };
+}
@@ -0,0 +1,613 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+// Behavioural model of the GEM for host tests. The register block lives in ordinary memory and
+// the driver under test accesses it through the pointer from `GemSim::ptr`. Nothing reacts to a
+// register access on its own: call `step` after driving the device and the model catches up
+// with everything written since the previous step, much as the hardware would.
+//
+// Limitations:
+// - Bit 31 of int_status, transmit_status and receive_status is reserved on the GEM. The model
+//   keeps it set between steps to tell a write-one-to-clear apart from an untouched register.
+// - Writes to int_enable and int_disable between two steps are applied disable first.
+// - Descriptor and buffer addresses are 32 bit. Their upper half is taken from
+//   upper_tx_q_base_addr and upper_rx_q_base_addr, so rings and buffers must share one 4 GiB
+//   window of the host address space.
+// - Transmission completes within a step, so TRANSMIT_GO is never seen set and a halt takes
+//   effect straight away.
+// - Interrupt moderation is not modelled, interrupts are raised as soon as their event occurs.
+// - Disabling and enabling receive again between two steps is only noticed through a new
+//   value in receive_q_ptr.
+// - Wake-on-LAN filters are not modelled, `raise` the WOL interrupt instead.
+// - Only broadcast and multicast frames are filtered, every unicast frame is received whatever
+//   its destination address.
+
+extern crate std;
+
+use std::boxed::Box;
+use std::collections::VecDeque;
+use std::vec::Vec;
+
+use core::ptr::{addr_of_mut, read_volatile, write_volatile};
+
+use tock_registers::fields::FieldValue;
+use tock_registers::LocalRegisterCopy;
+
+use zynqmp_pac::gem::*;
+
+use super::{
+    multicast_hash_index, GemInterrupts, MacAddress, RxDescriptor, RxRing, TxDescriptor, TxRing,
+};
+
+const BLOCK_WORDS: usize = core::mem::size_of::<RegisterBlock>() / 4;
+const UNTOUCHED: u32 = 1 << 31;
+
+// Values the ZynqMP GEM reports in its design configuration registers
+const REVISION: u32 = 0x0007_0107;
+const DESIGNCFG_DEBUG1: u32 = 0x0400_0000;
+const DESIGNCFG_DEBUG2: u32 = 0x7FF0_3FFF;
+const DESIGNCFG_DEBUG3: u32 = 0x0400_0000;
+const DESIGNCFG_DEBUG5: u32 = 0x002E_0100;
+const DESIGNCFG_DEBUG6: u32 = 0x0280_000E;
+const DESIGNCFG_DEBUG8: u32 = 0x1010_0404;
+
+macro_rules! reg {
+    ($sim:expr, $field:ident) => {{
+        let regs = $sim.regs;
+        // Safety: `regs` points to a live, RegisterBlock sized allocation
+        unsafe { addr_of_mut!((*regs).$field) as *mut u32 }
+    }};
+}
+
+pub const SIM_RING_LEN: usize = 8;
+pub const SIM_BUF_SIZE: usize = 1536;
+
+// Descriptor rings and buffers in a single allocation, so they share one 4 GiB window
+#[repr(C, align(64))]
+pub struct SimDma {
+    pub rx_descs: [RxDescriptor; SIM_RING_LEN],
+    pub tx_descs: [TxDescriptor; SIM_RING_LEN],
+    pub rx_bufs: [u8; SIM_RING_LEN * SIM_BUF_SIZE],
+    pub tx_bufs: [u8; SIM_RING_LEN * SIM_BUF_SIZE],
+}
+
+impl SimDma {
+    // Leaked so the rings can be handed to a driver for the rest of the test
+    pub fn leak() -> &'static mut Self {
+        Box::leak(Box::new(Self {
+            rx_descs: [const { RxDescriptor::new() }; SIM_RING_LEN],
+            tx_descs: [const { TxDescriptor::new() }; SIM_RING_LEN],
+            rx_bufs: [0; SIM_RING_LEN * SIM_BUF_SIZE],
+            tx_bufs: [0; SIM_RING_LEN * SIM_BUF_SIZE],
+        }))
+    }
+
+    pub fn rings(&'static mut self) -> (RxRing<'static>, TxRing<'static>) {
+        (
+            RxRing::new(&mut self.rx_descs, &mut self.rx_bufs, SIM_BUF_SIZE),
+            TxRing::new(&mut self.tx_descs, &mut self.tx_bufs, SIM_BUF_SIZE),
+        )
+    }
+}
+
+// A frame the model put on the wire
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimTxFrame {
+    pub data: Vec<u8>,
+    // Descriptors the frame was gathered from
+    pub descriptors: usize,
+}
+
+struct SimRxFrame {
+    data: Vec<u8>,
+    status: u32,
+    // Whether the last descriptor gets END_OF_FRAME
+    complete: bool,
+}
+
+pub struct GemSim {
+    regs: *mut RegisterBlock,
+    int_status: u32,
+    int_mask: u32,
+    tx_status: u32,
+    rx_status: u32,
+    tx_enabled: bool,
+    // Stopped by TX_HALT_PCLK or an error, until the next TX_START_PCLK
+    tx_halted: bool,
+    rx_enabled: bool,
+    // Stopped by an HRESP error, until reception is restarted
+    rx_stopped: bool,
+    // The frame at the head of the queue waits for a flush whatever the descriptors say
+    rx_stalled: bool,
+    tx_base: u32,
+    tx_cur: u32,
+    rx_base: u32,
+    rx_cur: u32,
+    rx_queue: VecDeque<SimRxFrame>,
+    tx_frames: VecDeque<SimTxFrame>,
+    tx_error: Option<u32>,
+    // Bus addresses are host addresses plus this
+    bus_offset: i64,
+}
+
+impl GemSim {
+    pub fn new() -> Self {
+        let regs = Box::into_raw(Box::new([0u32; BLOCK_WORDS])) as *mut RegisterBlock;
+        let mut sim = Self {
+            regs,
+            int_status: 0,
+            int_mask: GemInterrupts::all().bits(),
+            tx_status: 0,
+            rx_status: 0,
+            tx_enabled: false,
+            tx_halted: false,
+            rx_enabled: false,
+            rx_stopped: false,
+            rx_stalled: false,
+            tx_base: 0,
+            tx_cur: 0,
+            rx_base: 0,
+            rx_cur: 0,
+            rx_queue: VecDeque::new(),
+            tx_frames: VecDeque::new(),
+            tx_error: None,
+            bus_offset: 0,
+        };
+        sim.write(reg!(sim, revision_reg), REVISION);
+        sim.write(reg!(sim, designcfg_debug1), DESIGNCFG_DEBUG1);
+        sim.write(reg!(sim, designcfg_debug2), DESIGNCFG_DEBUG2);
+        sim.write(reg!(sim, designcfg_debug3), DESIGNCFG_DEBUG3);
+        sim.write(reg!(sim, designcfg_debug5), DESIGNCFG_DEBUG5);
+        sim.write(reg!(sim, designcfg_debug6), DESIGNCFG_DEBUG6);
+        sim.write(reg!(sim, designcfg_debug8), DESIGNCFG_DEBUG8);
+        sim.write(
+            reg!(sim, network_status),
+            network_status::MAN_DONE::SET.value,
+        );
+        sim.publish();
+        sim
+    }
+
+    pub fn ptr(&self) -> *mut RegisterBlock {
+        self.regs
+    }
+
+    // Raw register access by byte offset, e.g. to change the design configuration
+    pub fn read_reg(&self, offset: usize) -> u32 {
+        assert!(offset < BLOCK_WORDS * 4 && offset.is_multiple_of(4));
+        self.read((self.regs as *mut u32).wrapping_add(offset / 4))
+    }
+
+    pub fn write_reg(&mut self, offset: usize, value: u32) {
+        assert!(offset < BLOCK_WORDS * 4 && offset.is_multiple_of(4));
+        self.write((self.regs as *mut u32).wrapping_add(offset / 4), value);
+    }
+
+    pub fn pending_interrupts(&self) -> GemInterrupts {
+        GemInterrupts::from_bits_truncate(self.int_status)
+    }
+
+    // State of the interrupt line: any pending source which is not masked
+    pub fn irq(&self) -> bool {
+        self.int_status & !self.int_mask != 0
+    }
+
+    // Set interrupt sources, as an event outside the DMA paths would
+    pub fn raise(&mut self, irqs: GemInterrupts) {
+        self.int_status |= irqs.bits();
+        self.publish();
+    }
+
+    // Queue a frame, exactly as it should land in memory, for delivery on the next step
+    pub fn inject_rx(&mut self, frame: &[u8]) {
+        self.inject_rx_with_status(frame, 0);
+    }
+
+    // As `inject_rx`, with extra rx_desc_status bits such as the VLAN or checksum fields
+    pub fn inject_rx_with_status(&mut self, frame: &[u8], status: u32) {
+        self.rx_queue.push_back(SimRxFrame {
+            data: frame.to_vec(),
+            status,
+            complete: true,
+        });
+    }
+
+    // Queue a frame the controller starts writing but abandons, leaving its descriptors
+    // without END_OF_FRAME
+    pub fn inject_rx_aborted(&mut self, frame: &[u8]) {
+        self.rx_queue.push_back(SimRxFrame {
+            data: frame.to_vec(),
+            status: 0,
+            complete: false,
+        });
+    }
+
+    // Keep the next frame stuck in the packet buffer, reporting no descriptor available, until
+    // it is flushed
+    pub fn stall_rx(&mut self) {
+        self.rx_stalled = true;
+    }
+
+    // Stop the receive DMA as an HRESP error on the bus would
+    pub fn fail_rx_dma(&mut self) {
+        self.rx_stopped = true;
+        self.int_status |= GemInterrupts::RESP_NOT_OK.bits();
+        self.rx_status |= receive_status::RESP_NOT_OK::SET.value;
+        self.publish();
+    }
+
+    pub fn rx_queued(&self) -> usize {
+        self.rx_queue.len()
+    }
+
+    pub fn take_tx(&mut self) -> Option<SimTxFrame> {
+        self.tx_frames.pop_front()
+    }
+
+    // Fail the next frame sent with these tx_desc_status error bits instead of putting it on
+    // the wire
+    pub fn fail_next_tx(&mut self, error: FieldValue<u32, tx_desc_status::Register>) {
+        self.tx_error = Some(error.value);
+    }
+
+    // Have memory appear to the controller at host addresses plus `offset`, as rings set up
+    // with `DmaTranslation::Offset` expect
+    pub fn set_bus_offset(&mut self, offset: i64) {
+        self.bus_offset = offset;
+    }
+
+    // Lose every register and all internal state, as when the power domain goes down. Frames
+    // already on the wire stay available to `take_tx`, frames not yet received are gone.
+    pub fn power_cycle(&mut self) {
+        let mut fresh = Self::new();
+        fresh.tx_frames = core::mem::take(&mut self.tx_frames);
+        fresh.bus_offset = self.bus_offset;
+        // Safety: both point to live, RegisterBlock sized allocations
+        unsafe { core::ptr::copy_nonoverlapping(fresh.regs, self.regs, 1) };
+        // Keep the block the driver points at, the other one is freed with `fresh`
+        core::mem::swap(&mut fresh.regs, &mut self.regs);
+        core::mem::swap(self, &mut fresh);
+    }
+
+    pub fn step(&mut self) {
+        self.collect_writes();
+        self.run_control();
+        self.run_rx();
+        self.publish();
+    }
+
+    fn read(&self, reg: *mut u32) -> u32 {
+        // Safety: callers only pass pointers into the register block
+        unsafe { read_volatile(reg) }
+    }
+
+    fn write(&self, reg: *mut u32, value: u32) {
+        // Safety: callers only pass pointers into the register block
+        unsafe { write_volatile(reg, value) }
+    }
+
+    fn publish(&mut self) {
+        self.write(reg!(self, int_status), self.int_status | UNTOUCHED);
+        self.write(reg!(self, transmit_status), self.tx_status | UNTOUCHED);
+        self.write(reg!(self, receive_status), self.rx_status | UNTOUCHED);
+        self.write(reg!(self, int_mask), self.int_mask);
+        if self.tx_enabled {
+            self.write(reg!(self, transmit_q_ptr), self.tx_cur);
+        }
+        if self.rx_enabled {
+            self.write(reg!(self, receive_q_ptr), self.rx_cur);
+        }
+    }
+
+    // Write-one-to-clear of a status register. All ones is the only write which leaves the
+    // untouched marker set.
+    fn clear_on_write(&self, reg: *mut u32, status: u32) -> u32 {
+        let value = self.read(reg);
+        if value & UNTOUCHED == 0 || value == u32::MAX {
+            status & !value
+        } else {
+            status
+        }
+    }
+
+    fn collect_writes(&mut self) {
+        self.int_status = self.clear_on_write(reg!(self, int_status), self.int_status);
+        self.tx_status = self.clear_on_write(reg!(self, transmit_status), self.tx_status);
+        self.rx_status = self.clear_on_write(reg!(self, receive_status), self.rx_status);
+
+        let disable = self.read(reg!(self, int_disable));
+        let enable = self.read(reg!(self, int_enable));
+        self.int_mask = (self.int_mask | disable) & !enable & GemInterrupts::all().bits();
+        self.write(reg!(self, int_disable), 0);
+        self.write(reg!(self, int_enable), 0);
+
+        // The queue pointers can only be written while the direction is disabled
+        if !self.tx_enabled {
+            self.tx_base = self.read(reg!(self, transmit_q_ptr));
+        }
+        if !self.rx_enabled {
+            self.rx_base = self.read(reg!(self, receive_q_ptr));
+        } else if self.read(reg!(self, receive_q_ptr)) != self.rx_cur {
+            // Receive was disabled and enabled again on a new ring
+            self.rx_base = self.read(reg!(self, receive_q_ptr));
+            self.rx_cur = self.rx_base;
+            self.rx_stopped = false;
+        }
+    }
+
+    fn run_control(&mut self) {
+        let ctrl = LocalRegisterCopy::<u32, network_control::Register>::new(
+            self.read(reg!(self, network_control)),
+        );
+
+        let tx_enabled = ctrl.is_set(network_control::ENABLE_TRANSMIT);
+        if tx_enabled != self.tx_enabled {
+            // Disabling transmit rewinds the queue pointer to the base address
+            self.tx_cur = self.tx_base;
+            self.tx_enabled = tx_enabled;
+            if !tx_enabled {
+                self.write(reg!(self, transmit_q_ptr), self.tx_base);
+            }
+        }
+        let rx_enabled = ctrl.is_set(network_control::ENABLE_RECEIVE);
+        if rx_enabled != self.rx_enabled {
+            self.rx_cur = self.rx_base;
+            self.rx_enabled = rx_enabled;
+            if !rx_enabled {
+                self.write(reg!(self, receive_q_ptr), self.rx_base);
+            }
+        }
+
+        if ctrl.is_set(network_control::FLUSH_RX_PKT_PCLK) {
+            self.rx_queue.pop_front();
+            self.rx_stalled = false;
+        }
+        if ctrl.is_set(network_control::TX_HALT_PCLK) {
+            self.tx_halted = true;
+        }
+        if self.tx_halted {
+            // The queue pointer may be moved while halted, which also moves the wrap target
+            let q_ptr = self.read(reg!(self, transmit_q_ptr));
+            if q_ptr != self.tx_cur {
+                self.tx_base = q_ptr;
+                self.tx_cur = q_ptr;
+            }
+        }
+        if ctrl.is_set(network_control::TX_START_PCLK) && self.tx_enabled {
+            self.tx_halted = false;
+            self.run_tx();
+        }
+
+        // Self clearing strobes
+        let strobes = network_control::TX_START_PCLK::SET
+            + network_control::TX_HALT_PCLK::SET
+            + network_control::FLUSH_RX_PKT_PCLK::SET
+            + network_control::CLEAR_ALL_STATS_REGS::SET;
+        self.write(reg!(self, network_control), ctrl.get() & !strobes.value);
+    }
+
+    fn tx_addr(&self, addr: u32) -> *mut u32 {
+        let upper = self.read(reg!(self, upper_tx_q_base_addr)) as u64;
+        ((upper << 32) | addr as u64).wrapping_add_signed(-self.bus_offset) as usize as *mut u32
+    }
+
+    fn rx_addr(&self, addr: u32) -> *mut u32 {
+        let upper = self.read(reg!(self, upper_rx_q_base_addr)) as u64;
+        ((upper << 32) | addr as u64).wrapping_add_signed(-self.bus_offset) as usize as *mut u32
+    }
+
+    fn run_tx(&mut self) {
+        loop {
+            let first = self.tx_cur;
+            let mut cur = first;
+            let mut data = Vec::new();
+            let mut descriptors = 0;
+            loop {
+                let desc = self.tx_addr(cur);
+                let status = LocalRegisterCopy::<u32, tx_desc_status::Register>::new(
+                    self.read(desc.wrapping_add(1)),
+                );
+                if status.is_set(tx_desc_status::USED) {
+                    // Nothing more queued, or a frame was cut short by a used descriptor
+                    self.int_status |= GemInterrupts::TX_USED_BIT_READ.bits();
+                    self.tx_status |= transmit_status::USED_BIT_READ::SET.value;
+                    if descriptors != 0 {
+                        self.int_status |= GemInterrupts::TX_UNDERRUN.bits();
+                        self.tx_status |= transmit_status::TRANSMIT_UNDER_RUN::SET.value;
+                        self.tx_halted = true;
+                    }
+                    return;
+                }
+
+                let len = status.read(tx_desc_status::LENGTH) as usize;
+                let buf = self.tx_addr(self.read(desc)) as *const u8;
+                // Safety: the driver handed this buffer to the controller
+                data.extend_from_slice(unsafe { core::slice::from_raw_parts(buf, len) });
+                descriptors += 1;
+
+                cur = match status.is_set(tx_desc_status::WRAP) {
+                    true => self.tx_base,
+                    false => cur + 8,
+                };
+                if status.is_set(tx_desc_status::LAST_BUFFER) {
+                    break;
+                }
+            }
+
+            // The controller only marks the first descriptor of a frame as used, along with
+            // any error
+            let error = self.tx_error.take().unwrap_or(0);
+            let desc = self.tx_addr(first).wrapping_add(1);
+            self.write(
+                desc,
+                self.read(desc) | tx_desc_status::USED::SET.value | error,
+            );
+            self.tx_cur = cur;
+            if error == 0 {
+                self.tx_frames.push_back(SimTxFrame { data, descriptors });
+                self.int_status |= GemInterrupts::TX_COMPLETE.bits();
+                self.tx_status |= transmit_status::TRANSMIT_COMPLETE::SET.value;
+            } else if error & tx_desc_status::AMBA_ERROR::SET.value != 0 {
+                self.int_status |= GemInterrupts::AMBA_ERROR.bits();
+                self.tx_status |= transmit_status::AMBA_ERROR::SET.value;
+            } else {
+                self.int_status |= GemInterrupts::RETRY_LIMIT_OR_LATE_COLLISION.bits();
+                self.tx_status |= transmit_status::RETRY_LIMIT_EXCEEDED::SET.value;
+            }
+            // Transmission stops after an error until software restarts it
+            if error != 0 {
+                self.tx_halted = true;
+                return;
+            }
+        }
+    }
+
+    fn rx_buf_size(&self) -> usize {
+        let dma =
+            LocalRegisterCopy::<u32, dma_config::Register>::new(self.read(reg!(self, dma_config)));
+        dma.read(dma_config::RX_BUF_SIZE) as usize * super::RX_BUF_UNIT
+    }
+
+    fn rx_offset(&self) -> usize {
+        let cfg = LocalRegisterCopy::<u32, network_config::Register>::new(
+            self.read(reg!(self, network_config)),
+        );
+        cfg.read(network_config::RECEIVE_BUFFER_OFFSET) as usize
+    }
+
+    // Whether the address filters let a frame through to memory
+    fn accepts(&self, frame: &[u8]) -> bool {
+        let cfg = LocalRegisterCopy::<u32, network_config::Register>::new(
+            self.read(reg!(self, network_config)),
+        );
+        let Some(dest) = frame.get(..6) else {
+            return true;
+        };
+        if cfg.is_set(network_config::COPY_ALL_FRAMES) || dest[0] & 1 == 0 {
+            return true;
+        }
+        if dest == [0xFF; 6] {
+            return !cfg.is_set(network_config::NO_BROADCAST);
+        }
+        let hash = (self.read(reg!(self, hash_top)) as u64) << 32
+            | self.read(reg!(self, hash_bottom)) as u64;
+        let mut mac = [0; 6];
+        mac.copy_from_slice(dest);
+        cfg.is_set(network_config::MULTICAST_HASH_ENABLE)
+            && hash & 1 << multicast_hash_index(&MacAddress::new(mac)) != 0
+    }
+
+    fn run_rx(&mut self) {
+        if !self.rx_enabled || self.rx_stopped {
+            return;
+        }
+        while let Some(frame) = self.rx_queue.pop_front() {
+            if !self.accepts(&frame.data) {
+                continue;
+            }
+            if self.rx_stalled || !self.deliver(&frame) {
+                self.rx_queue.push_front(frame);
+                self.int_status |= GemInterrupts::RX_USED_BIT_READ.bits();
+                self.rx_status |= receive_status::BUFFER_NOT_AVAILABLE::SET.value;
+                return;
+            }
+            self.int_status |= GemInterrupts::RX_COMPLETE.bits();
+            self.rx_status |= receive_status::FRAME_RECEIVED::SET.value;
+        }
+    }
+
+    // Spread a frame over as many descriptors as it needs. Returns false, leaving the ring
+    // untouched, when the driver has not handed enough of them back.
+    fn deliver(&mut self, rx: &SimRxFrame) -> bool {
+        let frame = &rx.data[..];
+        let buf_size = self.rx_buf_size();
+        let offset = self.rx_offset();
+
+        let mut needed = Vec::new();
+        let mut cur = self.rx_cur;
+        let mut remaining = frame.len() + offset;
+        loop {
+            let desc = self.rx_addr(cur);
+            let addr = LocalRegisterCopy::<u32, rx_desc_addr::Register>::new(self.read(desc));
+            if addr.matches_all(rx_desc_addr::OWNERSHIP::Software) {
+                return false;
+            }
+            needed.push(cur);
+            cur = match addr.is_set(rx_desc_addr::WRAP) {
+                true => self.rx_base,
+                false => cur + 8,
+            };
+            if remaining <= buf_size {
+                break;
+            }
+            remaining -= buf_size;
+        }
+
+        let mut written = 0;
+        let count = needed.len();
+        for (i, &desc_addr) in needed.iter().enumerate() {
+            let desc = self.rx_addr(desc_addr);
+            let addr = LocalRegisterCopy::<u32, rx_desc_addr::Register>::new(self.read(desc));
+            let start = if i == 0 { offset } else { 0 };
+            let len = (buf_size - start).min(frame.len() - written);
+            let buf = self.rx_addr(addr.read(rx_desc_addr::ADDRESS) << 2) as *mut u8;
+            // Safety: the driver handed this buffer to the controller
+            unsafe {
+                core::ptr::copy_nonoverlapping(frame[written..].as_ptr(), buf.add(start), len)
+            };
+            written += len;
+
+            let mut status: FieldValue<u32, rx_desc_status::Register> =
+                rx_desc_status::START_OF_FRAME.val((i == 0) as u32);
+            if i == count - 1 && rx.complete {
+                status += rx_desc_status::END_OF_FRAME::SET
+                    + rx_desc_status::LENGTH.val(frame.len() as u32);
+            }
+            self.write(desc.wrapping_add(1), status.value | rx.status);
+            self.write(desc, addr.get() | rx_desc_addr::OWNERSHIP::Software.value);
+        }
+        self.rx_cur = cur;
+        true
+    }
+}
+
+impl Default for GemSim {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for GemSim {
+    fn drop(&mut self) {
+        // Safety: allocated in `new` from a box of the same type
+        drop(unsafe { Box::from_raw(self.regs as *mut [u32; BLOCK_WORDS]) });
+    }
+}
+
+// A running driver on `rx` and `tx` for the tests, the device initialised with `config` and
+// the RX buffer size of `rx`
+#[cfg(test)]
+pub(crate) fn driver_with<'a>(
+    sim: &mut GemSim,
+    config: super::GemConfig,
+    rx: RxRing<'a>,
+    tx: TxRing<'a>,
+) -> super::Driver<'a> {
+    let dev = unsafe { super::Device::new(sim.ptr()) }
+        .init(config.rx_buf_size(rx.buf_size()))
+        .unwrap();
+    sim.step();
+    let driver = super::Driver::new(dev.phy_complete(), rx, tx).unwrap();
+    sim.step();
+    driver
+}
+
+// As `driver_with`, with the default configuration on rings of their own
+#[cfg(test)]
+pub(crate) fn driver(sim: &mut GemSim) -> super::Driver<'static> {
+    let (rx, tx) = SimDma::leak().rings();
+    driver_with(sim, super::GemConfig::new(), rx, tx)
+}
@@ -0,0 +1,83 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use smoltcp::phy::{self, Checksum, ChecksumCapabilities, DeviceCapabilities, Medium};
+use smoltcp::time::Instant;
+
+use super::checksum::{checksum_capabilities, ChecksumOffload};
+use super::driver::{Driver, RxToken, TxToken};
+
+impl<'a, I> phy::Device for Driver<'a, I> {
+    type RxToken<'d>
+        = RxToken<'d, 'a>
+    where
+        Self: 'd;
+    type TxToken<'d>
+        = TxToken<'d, 'a, I>
+    where
+        Self: 'd;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        Driver::receive_tokens(self)
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        Driver::transmit_token(self)
+    }
+
+    fn capabilities(&self) -> DeviceCapabilities {
+        let checksum = checksum_capabilities(self.checksum_offload());
+
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ethernet;
+        caps.max_transmission_unit = self.mtu();
+        caps.max_burst_size = Some(self.tx_ring_len());
+        caps.checksum = checksum;
+        caps
+    }
+}
+
+checksum_capabilities!(Checksum, ChecksumCapabilities);
+
+impl phy::RxToken for RxToken<'_, '_> {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&[u8]) -> R,
+    {
+        RxToken::consume(self, |buf| f(buf))
+    }
+}
+
+impl<I> phy::TxToken for TxToken<'_, '_, I> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        // The stack keeps to the MTU reported, which always fits a TX buffer
+        TxToken::consume(self, len, f).expect("frame larger than the MTU")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn received_transport_checksums_left_to_the_stack() {
+        let caps = checksum_capabilities(ChecksumOffload { rx: true, tx: true });
+        assert!(!caps.ipv4.rx() && !caps.ipv4.tx());
+        for checksum in [caps.tcp, caps.udp] {
+            assert!(checksum.rx() && !checksum.tx());
+        }
+
+        let caps = checksum_capabilities(ChecksumOffload {
+            rx: true,
+            tx: false,
+        });
+        assert!(!caps.ipv4.rx() && caps.ipv4.tx());
+        assert!(caps.tcp.rx() && caps.tcp.tx());
+    }
+}
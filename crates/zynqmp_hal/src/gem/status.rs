@@ -0,0 +1,170 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use tock_registers::LocalRegisterCopy;
+
+use zynqmp_pac::gem::*;
+
+use super::checksum::RxChecksum;
+use super::interrupts::flag_set;
+use super::vlan::VlanTag;
+
+flag_set! {
+    // Conditions latched in receive_status
+    RecvStatus {
+        FRAME_RECEIVED = receive_status::FRAME_RECEIVED::SET.value,
+        RESP_NOT_OK = receive_status::RESP_NOT_OK::SET.value,
+        FIFO_OVERFLOW = receive_status::RECEIVE_OVERRUN::SET.value,
+        UNAVAILABLE_BUFFER = receive_status::BUFFER_NOT_AVAILABLE::SET.value,
+    }
+}
+
+flag_set! {
+    // Conditions latched in transmit_status
+    TxStatus {
+        USED_BIT_READ = transmit_status::USED_BIT_READ::SET.value,
+        COLLISION = transmit_status::COLLISION_OCCURRED::SET.value,
+        RETRY_LIMIT_EXCEEDED = transmit_status::RETRY_LIMIT_EXCEEDED::SET.value,
+        TRANSMIT_GO = transmit_status::TRANSMIT_GO::SET.value,
+        AMBA_ERROR = transmit_status::AMBA_ERROR::SET.value,
+        TRANSMIT_COMPLETE = transmit_status::TRANSMIT_COMPLETE::SET.value,
+        UNDERRUN = transmit_status::TRANSMIT_UNDER_RUN::SET.value,
+        LATE_COLLISION = transmit_status::LATE_COLLISION_OCCURRED::SET.value,
+        RESP_NOT_OK = transmit_status::RESP_NOT_OK::SET.value,
+    }
+}
+
+impl TxStatus {
+    // Conditions which stop transmission until it is recovered. A used bit read is only an
+    // error together with an underrun, i.e. when the controller ran out of descriptors mid
+    // frame.
+    pub const ERRORS: Self = Self(
+        Self::RETRY_LIMIT_EXCEEDED.0
+            | Self::AMBA_ERROR.0
+            | Self::UNDERRUN.0
+            | Self::LATE_COLLISION.0
+            | Self::RESP_NOT_OK.0,
+    );
+}
+
+// What the controller reported about a received frame in word 1 of its descriptor. For frames
+// spanning several buffers the length and end of frame fields come from the last descriptor,
+// everything else from the first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RxFrameInfo {
+    // Only valid with `end_of_frame` set
+    pub len: usize,
+    pub start_of_frame: bool,
+    pub end_of_frame: bool,
+    // Only reported while the MAC is set to ignore the FCS
+    pub bad_fcs: bool,
+    pub broadcast: bool,
+    pub multicast_hash_match: bool,
+    pub unicast_hash_match: bool,
+    // Specific address filter which matched, from 0
+    pub specific_address_match: Option<u8>,
+    // Type ID register which matched, from 0. Not reported with checksum offload enabled.
+    pub type_id_match: Option<u8>,
+    pub vlan: Option<VlanTag>,
+    pub checksum: RxChecksum,
+}
+
+impl RxFrameInfo {
+    // Bits 24:22 carry the type ID match or the checksum result depending on `rx_offload`.
+    // The VLAN ID is read from the start of the frame in `frame`.
+    pub(crate) fn new(
+        status: LocalRegisterCopy<u32, rx_desc_status::Register>,
+        rx_offload: bool,
+        frame: &[u8],
+    ) -> Self {
+        let specific_address_match = match status.is_set(rx_desc_status::SPEC_ADD_MATCH) {
+            true => Some(status.read(rx_desc_status::SPEC_ADD_REGISTER) as u8),
+            false => None,
+        };
+        let (type_id_match, checksum) = match rx_offload {
+            true => (None, RxChecksum::from_status(status)),
+            false if status.is_set(rx_desc_status::TYPE_ID_MATCH) => (
+                Some(status.read(rx_desc_status::TYPE_ID_REGISTER) as u8),
+                RxChecksum::NotChecked,
+            ),
+            false => (None, RxChecksum::NotChecked),
+        };
+
+        Self {
+            len: status.read(rx_desc_status::LENGTH) as usize,
+            start_of_frame: status.is_set(rx_desc_status::START_OF_FRAME),
+            end_of_frame: status.is_set(rx_desc_status::END_OF_FRAME),
+            bad_fcs: status.is_set(rx_desc_status::BAD_FCS),
+            broadcast: status.is_set(rx_desc_status::BROADCAST),
+            multicast_hash_match: status.is_set(rx_desc_status::MULTICAST_HASH_MATCH),
+            unicast_hash_match: status.is_set(rx_desc_status::UNICAST_HASH_MATCH),
+            specific_address_match,
+            type_id_match,
+            vlan: VlanTag::from_frame(status, frame),
+            checksum,
+        }
+    }
+
+    // A frame spanning several buffers, from the word 1 of its `first` and `last` descriptors
+    pub(crate) fn from_chain(
+        first: LocalRegisterCopy<u32, rx_desc_status::Register>,
+        last: LocalRegisterCopy<u32, rx_desc_status::Register>,
+        rx_offload: bool,
+        frame: &[u8],
+    ) -> Self {
+        Self {
+            len: last.read(rx_desc_status::LENGTH) as usize,
+            end_of_frame: last.is_set(rx_desc_status::END_OF_FRAME),
+            bad_fcs: last.is_set(rx_desc_status::BAD_FCS),
+            ..Self::new(first, rx_offload, frame)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info(status: u32, rx_offload: bool) -> RxFrameInfo {
+        let mut frame = [0u8; 18];
+        frame[12..16].copy_from_slice(&[0x81, 0x00, 0x20, 0x2A]);
+        RxFrameInfo::new(LocalRegisterCopy::new(status), rx_offload, &frame)
+    }
+
+    #[test]
+    fn word1_decoding() {
+        // Broadcast, specific address 2, VLAN 42 priority 1, SOF/EOF, 60 bytes
+        let status = (1 << 31) | (1 << 27) | (2 << 25) | (1 << 21) | (1 << 17) | 0xC03C;
+        let plain = info(status | (1 << 24) | (3 << 22), false);
+        assert_eq!(plain.len, 60);
+        assert!(plain.start_of_frame && plain.end_of_frame && plain.broadcast);
+        assert!(!plain.bad_fcs && !plain.unicast_hash_match);
+        assert_eq!(plain.specific_address_match, Some(2));
+        assert_eq!(plain.type_id_match, Some(3));
+        assert_eq!(plain.checksum, RxChecksum::NotChecked);
+        assert_eq!(plain.vlan.map(|v| (v.id, v.priority)), Some((42, 1)));
+
+        // With offload the same bits are the checksum result
+        let offload = info(status | (2 << 22), true);
+        assert_eq!(offload.type_id_match, None);
+        assert_eq!(offload.checksum, RxChecksum::IpTcp);
+
+        // Across buffers, only the length and end of frame come from the last descriptor
+        let first = LocalRegisterCopy::new(status & !(1 << 15));
+        let last = LocalRegisterCopy::new((1 << 15) | (1 << 13) | 3000);
+        let mut frame = [0u8; 18];
+        frame[12..16].copy_from_slice(&[0x81, 0x00, 0x20, 0x2A]);
+        let chain = RxFrameInfo::from_chain(first, last, false, &frame);
+        assert_eq!(chain.len, 3000);
+        assert!(chain.start_of_frame && chain.end_of_frame && chain.bad_fcs && chain.broadcast);
+        assert_eq!(chain.specific_address_match, Some(2));
+        assert_eq!(chain.vlan.map(|v| v.id), Some(42));
+
+        let flags = RecvStatus::from_bits_truncate(0xFFFF_FFFF);
+        assert_eq!(flags, RecvStatus::all());
+        assert!(flags.contains(RecvStatus::FRAME_RECEIVED | RecvStatus::FIFO_OVERFLOW));
+    }
+}
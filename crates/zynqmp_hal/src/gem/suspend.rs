@@ -0,0 +1,302 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+// Taking the controller through a power down of its domain. Everything software programmed is
+// saved on suspend and written back on resume, statistics and timestamps latched by the
+// hardware are not.
+
+use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
+
+use zynqmp_pac::gem::*;
+
+use super::interrupts::GemInterrupts;
+use super::{Device, Running, Suspended};
+
+// Frames which wake the system while suspended, raising the WOL interrupt
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct WakeOnLan {
+    pub magic_packet: bool,
+    // ARP requests for an IPv4 address with these lower 16 bits
+    pub arp: Option<u16>,
+    // Frames to the address in spec_add1
+    pub specific_address: bool,
+    // Multicast frames which pass the hash filter
+    pub multicast_hash: bool,
+}
+
+impl WakeOnLan {
+    fn register_value(&self) -> u32 {
+        (wol_register::WOL_MASK_0.val(self.magic_packet as u32)
+            + wol_register::WOL_MASK_1.val(self.arp.is_some() as u32)
+            + wol_register::WOL_MASK_2.val(self.specific_address as u32)
+            + wol_register::WOL_MASK_3.val(self.multicast_hash as u32)
+            + wol_register::ADDR.val(self.arp.unwrap_or(0) as u32))
+        .value
+    }
+}
+
+macro_rules! saved_registers {
+    ($($reg:ident,)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        struct Registers {
+            $($reg: u32,)*
+        }
+
+        impl Registers {
+            fn save(regs: &RegisterBlock) -> Self {
+                Self {
+                    $($reg: regs.$reg.get(),)*
+                }
+            }
+
+            // In the order listed, so e.g. the bottom half of a specific address goes in first
+            fn restore(&self, regs: &RegisterBlock) {
+                $(regs.$reg.set(self.$reg);)*
+            }
+        }
+    };
+}
+
+saved_registers! {
+    network_config,
+    dma_config,
+    tx_pause_quantum,
+    pbuf_txcutthru,
+    pbuf_rxcutthru,
+    jumbo_max_length,
+    axi_max_pipeline,
+    int_moderation,
+    dma_addr_or_mask,
+    stretch_ratio,
+    // Filters
+    hash_bottom,
+    hash_top,
+    spec_add1_bottom,
+    spec_add1_top,
+    spec_add2_bottom,
+    spec_add2_top,
+    spec_add3_bottom,
+    spec_add3_top,
+    spec_add4_bottom,
+    spec_add4_top,
+    spec_type1,
+    spec_type2,
+    spec_type3,
+    spec_type4,
+    mask_add1_bottom,
+    mask_add1_top,
+    stacked_vlan,
+    // Screeners
+    screening_type_1_register_0,
+    screening_type_1_register_1,
+    screening_type_1_register_2,
+    screening_type_1_register_3,
+    screening_type_2_register_0,
+    screening_type_2_register_1,
+    screening_type_2_register_2,
+    screening_type_2_register_3,
+    screening_type_2_ethertype_reg_0,
+    screening_type_2_ethertype_reg_1,
+    screening_type_2_ethertype_reg_2,
+    screening_type_2_ethertype_reg_3,
+    type2_compare_0_word_0,
+    type2_compare_0_word_1,
+    type2_compare_1_word_0,
+    type2_compare_1_word_1,
+    type2_compare_2_word_0,
+    type2_compare_2_word_1,
+    type2_compare_3_word_0,
+    type2_compare_3_word_1,
+    // Timestamp unit. The timer restarts from the time it was suspended at, so it needs
+    // synchronising again after a resume.
+    rx_ptp_unicast,
+    tx_ptp_unicast,
+    tsu_timer_incr_sub_nsec,
+    tsu_timer_incr,
+    tsu_nsec_cmp,
+    tsu_sec_cmp,
+    tsu_msb_sec_cmp,
+    tsu_timer_msb_sec,
+    tsu_timer_sec,
+    tsu_timer_nsec,
+    // Priority queue 1, pointing at a dummy descriptor when unused
+    transmit_q1_ptr,
+    receive_q1_ptr,
+    tx_bd_control,
+    rx_bd_control,
+}
+
+// What `Device::suspend` saved, for `Device::resume` to write back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SavedState {
+    registers: Registers,
+    // Without the enables and strobes
+    network_control: u32,
+    interrupts: GemInterrupts,
+    rx_queue: usize,
+    tx_queue: usize,
+}
+
+impl SavedState {
+    // Restart the queues at these descriptors instead of where the DMA stopped. The controller
+    // wraps back to the address the queue was started at, so rings which are not restarted at
+    // their base need this.
+    pub fn with_queues(mut self, rx: usize, tx: usize) -> Self {
+        self.rx_queue = rx;
+        self.tx_queue = tx;
+        self
+    }
+}
+
+const CONTROL_ENABLES: u32 = network_control::ENABLE_RECEIVE::SET.value
+    | network_control::ENABLE_TRANSMIT::SET.value
+    | network_control::TX_START_PCLK::SET.value
+    | network_control::TX_HALT_PCLK::SET.value
+    | network_control::FLUSH_RX_PKT_PCLK::SET.value
+    | network_control::CLEAR_ALL_STATS_REGS::SET.value;
+
+impl<I> Device<Running, I> {
+    // Let the frames already queued go out, stop the controller and save what it loses when
+    // its power domain goes down. With `wol` the MAC keeps receiving and only the WOL
+    // interrupt stays enabled, frames landing in the RX ring meanwhile are not delivered. The
+    // device is handed back untouched if transmission does not finish.
+    pub fn suspend(
+        self,
+        wol: Option<WakeOnLan>,
+    ) -> Result<(Device<Suspended, I>, SavedState), Self> {
+        // The DMA stops by itself at the first descriptor software still owns
+        if !self.wait_tx_idle() {
+            return Err(self);
+        }
+        self.disable_tx();
+
+        let saved = SavedState {
+            registers: Registers::save(&self),
+            network_control: self.network_control.get() & !CONTROL_ENABLES,
+            interrupts: self.enabled_interrupts(),
+            rx_queue: ((self.upper_rx_q_base_addr.get() as u64) << 32
+                | self.receive_q_ptr.read(receive_q_ptr::DMA_RX_Q_PTR) as u64)
+                as usize,
+            tx_queue: ((self.upper_tx_q_base_addr.get() as u64) << 32
+                | self.transmit_q_ptr.read(transmit_q_ptr::DMA_TX_Q_PTR) as u64)
+                as usize,
+        };
+
+        self.disable_interrupts(GemInterrupts::all());
+        match wol {
+            Some(wol) => {
+                self.wol_register.set(wol.register_value());
+                self.clear_interrupts(GemInterrupts::WOL);
+                self.enable_interrupts(GemInterrupts::WOL);
+            }
+            None => {
+                self.wol_register.set(0);
+                self.disable_rx();
+            }
+        }
+        Ok((self.transition(), saved))
+    }
+}
+
+impl<I> Device<Suspended, I> {
+    // Whether a frame matching the wake-on-LAN filters arrived
+    pub fn woken(&self) -> bool {
+        self.int_status.is_set(int_status::WOL_INTERRUPT)
+    }
+
+    // Program everything `suspend` saved, whether or not the power actually went, and start
+    // transmit and receive again
+    #[must_use]
+    pub fn resume(self, saved: &SavedState) -> Device<Running, I> {
+        self.wol_register.set(0);
+        self.int_disable.set(GemInterrupts::all().bits());
+        self.network_control.set(saved.network_control);
+        saved.registers.restore(&self);
+        self.write_rx_queue(saved.rx_queue);
+        self.write_tx_queue(saved.tx_queue);
+
+        self.transmit_status.set(0xFFFF_FFFF);
+        self.receive_status.set(0xFFFF_FFFF);
+        self.int_status.set(0xFFFF_FFFF);
+        self.int_enable.set(saved.interrupts.bits());
+        self.network_control
+            .modify(network_control::ENABLE_RECEIVE::SET + network_control::ENABLE_TRANSMIT::SET);
+        self.transition()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gem::sim::{driver, GemSim};
+
+    #[test]
+    fn state_survives_power_down() {
+        let mut sim = GemSim::new();
+        let mut driver = driver(&mut sim);
+        let dev = driver.device();
+        dev.hash_top.set(0x8000_0001);
+        dev.spec_add2_bottom.set(0x0403_0201);
+        dev.spec_add2_top.set(0x0605);
+        dev.screening_type_1_register_0.set(0x0012_0003);
+        dev.tsu_timer_incr.set(8);
+        dev.enable_interrupts(GemInterrupts::RX_COMPLETE | GemInterrupts::TX_COMPLETE);
+        let network_config = dev.network_config.get();
+        sim.step();
+
+        driver.transmit(64, |buf| buf.fill(0x11)).unwrap();
+        sim.step();
+        let wol = WakeOnLan {
+            magic_packet: true,
+            arp: Some(0x0A01),
+            ..Default::default()
+        };
+        // Refused while the DMA keeps transmitting
+        let status = sim.read_reg(0x14);
+        sim.write_reg(0x14, status | transmit_status::TRANSMIT_GO::SET.value);
+        let Err(driver) = driver.suspend(Some(wol)) else {
+            panic!("suspended while transmitting");
+        };
+        sim.write_reg(0x14, status);
+
+        let Ok(suspended) = driver.suspend(Some(wol)) else {
+            panic!("not suspended");
+        };
+        sim.step();
+        assert_eq!(sim.take_tx().unwrap().data, [0x11; 64]);
+        assert_eq!(sim.read_reg(0xB8), 0x0003_0A01);
+        assert_eq!(
+            !GemInterrupts::from_bits_truncate(sim.read_reg(0x30)),
+            GemInterrupts::WOL
+        );
+        sim.raise(GemInterrupts::WOL);
+        sim.step();
+        assert!(suspended.device().woken());
+
+        sim.power_cycle();
+        assert_eq!(sim.read_reg(0x84), 0);
+        let mut driver = suspended.resume();
+        sim.step();
+        let dev = driver.device();
+        assert_eq!(dev.network_config.get(), network_config);
+        assert_eq!(dev.hash_top.get(), 0x8000_0001);
+        assert_eq!(dev.spec_add2_top.get(), 0x0605);
+        assert_eq!(dev.screening_type_1_register_0.get(), 0x0012_0003);
+        assert_eq!(dev.tsu_timer_incr.get(), 8);
+        assert_eq!(dev.wol_register.get(), 0);
+        assert_eq!(
+            dev.enabled_interrupts(),
+            GemInterrupts::RX_COMPLETE | GemInterrupts::TX_COMPLETE
+        );
+
+        sim.inject_rx(&[0x22; 64]);
+        sim.step();
+        assert_eq!(&driver.receive().unwrap()[..], [0x22; 64]);
+        driver.transmit(64, |buf| buf.fill(0x33)).unwrap();
+        sim.step();
+        assert_eq!(sim.take_tx().unwrap().data, [0x33; 64]);
+    }
+}
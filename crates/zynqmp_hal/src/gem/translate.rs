@@ -0,0 +1,86 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+// How the controller sees memory the CPU reaches at a given address. Needed when rings and
+// buffers live in remapped memory, e.g. in a guest whose physical addresses differ from the
+// bus addresses, or behind an IOMMU.
+#[derive(Clone, Copy, Default)]
+pub enum DmaTranslation<'a> {
+    // The controller uses CPU addresses as they are
+    #[default]
+    Identity,
+    // Bus addresses are CPU addresses plus this offset
+    Offset(i64),
+    // The controller forces bits 31:28 of buffer addresses itself through dma_addr_or_mask:
+    // each bit set in `enable` to the matching bit of `value`. Descriptor fetches are not
+    // affected.
+    OrMask {
+        value: u8,
+        enable: u8,
+    },
+    // Any other mapping, e.g. a walk of the stage 2 or IOMMU tables. Each buffer has to be
+    // contiguous in bus address space.
+    Map(&'a dyn Fn(usize) -> u64),
+}
+
+impl DmaTranslation<'_> {
+    // Address the controller reaches buffer memory at `addr` through
+    pub fn bus_addr(&self, addr: usize) -> u64 {
+        match *self {
+            DmaTranslation::Identity => addr as u64,
+            DmaTranslation::Offset(offset) => (addr as u64).wrapping_add_signed(offset),
+            DmaTranslation::OrMask { value, enable } => {
+                let enable = (enable as u64 & 0xF) << 28;
+                (addr as u64 & !enable) | ((value as u64) << 28 & enable)
+            }
+            DmaTranslation::Map(map) => map(addr),
+        }
+    }
+
+    // Address the controller fetches descriptors at `addr` from
+    pub fn desc_addr(&self, addr: usize) -> u64 {
+        match self {
+            DmaTranslation::OrMask { .. } => addr as u64,
+            _ => self.bus_addr(addr),
+        }
+    }
+
+    // dma_addr_or_mask MASK_VALUE and MASK_ENABLE
+    pub(crate) fn or_mask(&self) -> (u32, u32) {
+        match *self {
+            DmaTranslation::OrMask { value, enable } => (value as u32 & 0xF, enable as u32 & 0xF),
+            _ => (0, 0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bus_addresses() {
+        let addr = 0x1_2345_6780;
+        assert_eq!(DmaTranslation::Identity.bus_addr(addr), 0x1_2345_6780);
+        assert_eq!(
+            DmaTranslation::Offset(-0x1_0000_0000).bus_addr(addr),
+            0x2345_6780
+        );
+
+        let mask = DmaTranslation::OrMask {
+            value: 0b1000,
+            enable: 0b1001,
+        };
+        assert_eq!(mask.bus_addr(addr), 0x1_A345_6780);
+        assert_eq!(mask.desc_addr(addr), 0x1_2345_6780);
+        assert_eq!(mask.or_mask(), (0b1000, 0b1001));
+
+        let map = |addr: usize| addr as u64 | 0x8_0000_0000;
+        let map = DmaTranslation::Map(&map);
+        assert_eq!(map.desc_addr(0x1000), 0x8_0000_1000);
+        assert_eq!(map.or_mask(), (0, 0));
+    }
+}
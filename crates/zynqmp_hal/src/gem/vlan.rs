@@ -0,0 +1,105 @@
+//
+// Copyright 2024, DornerWorks
+//
+// SPDX-License-Identifier: BSD-2-Clause
+//
+
+use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
+use tock_registers::LocalRegisterCopy;
+
+use zynqmp_pac::gem::*;
+
+use super::{Device, Filterable};
+
+pub const TPID_VLAN: u16 = 0x8100;
+pub const TPID_QINQ: u16 = 0x88A8;
+
+// Offset of the tag control information of the outermost tag in a frame
+const TCI_OFFSET: usize = 14;
+
+// VLAN tag of a received frame, as reported by the RX descriptor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VlanTag {
+    // VLAN ID of the outermost tag, 0 for priority tagged frames
+    pub id: u16,
+    pub priority: u8,
+    pub cfi: bool,
+}
+
+impl VlanTag {
+    pub(crate) fn from_frame(
+        status: LocalRegisterCopy<u32, rx_desc_status::Register>,
+        frame: &[u8],
+    ) -> Option<Self> {
+        let priority_tagged = status.is_set(rx_desc_status::PRIORITY_TAG);
+        if !status.is_set(rx_desc_status::VLAN_TAG) && !priority_tagged {
+            return None;
+        }
+
+        // The descriptor only carries the priority and CFI, the ID comes from the frame itself
+        let id = match frame.get(TCI_OFFSET..TCI_OFFSET + 2) {
+            Some(tci) if !priority_tagged => u16::from_be_bytes([tci[0], tci[1]]) & 0x0FFF,
+            _ => 0,
+        };
+        Some(Self {
+            id,
+            priority: status.read(rx_desc_status::VLAN_PRIORITY) as u8,
+            cfi: status.is_set(rx_desc_status::CFI),
+        })
+    }
+}
+
+impl<S: Filterable, I> Device<S, I> {
+    // Drop every frame which does not carry a VLAN tag
+    pub fn set_vlan_only(&self, enable: bool) {
+        self.network_config
+            .modify(network_config::DISCARD_NON_VLAN_FRAMES.val(enable as u32));
+    }
+
+    // Q-in-Q: also treat frames whose outer tag uses `tpid` as VLAN tagged, so they are kept in
+    // VLAN only mode and their outer tag is reported in the RX descriptor
+    pub fn enable_stacked_vlan(&self, tpid: u16) {
+        self.stacked_vlan
+            .write(stacked_vlan::ENABLE_PROCESSING::SET + stacked_vlan::MATCH.val(tpid as u32));
+    }
+
+    pub fn disable_stacked_vlan(&self) {
+        self.stacked_vlan
+            .write(stacked_vlan::ENABLE_PROCESSING::CLEAR + stacked_vlan::MATCH.val(0));
+    }
+
+    pub fn stacked_vlan_tpid(&self) -> Option<u16> {
+        let reg = self.stacked_vlan.extract();
+        reg.is_set(stacked_vlan::ENABLE_PROCESSING)
+            .then(|| reg.read(stacked_vlan::MATCH) as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gem::sim::{driver, GemSim};
+
+    #[test]
+    fn vlan_filtering_programmed() {
+        let mut sim = GemSim::new();
+        let driver = driver(&mut sim);
+        let dev = driver.device();
+
+        let config = sim.read_reg(0x04);
+        dev.set_vlan_only(true);
+        assert_eq!(sim.read_reg(0x04), config | 1 << 2);
+        dev.set_vlan_only(false);
+        assert_eq!(sim.read_reg(0x04), config & !(1 << 2));
+
+        assert_eq!(dev.stacked_vlan_tpid(), None);
+        dev.enable_stacked_vlan(TPID_QINQ);
+        assert_eq!(sim.read_reg(0xC0), 0x8000_88A8);
+        assert_eq!(dev.stacked_vlan_tpid(), Some(TPID_QINQ));
+        dev.enable_stacked_vlan(TPID_VLAN);
+        assert_eq!(dev.stacked_vlan_tpid(), Some(TPID_VLAN));
+        dev.disable_stacked_vlan();
+        assert_eq!(sim.read_reg(0xC0), 0);
+        assert_eq!(dev.stacked_vlan_tpid(), None);
+    }
+}
@@ -9,7 +9,7 @@ use core::ops::Deref;
 use tock_registers::interfaces::{ReadWriteable, Readable, Writeable};
 
 use zynqmp_pac::uart::{
-    Channel_sts, Control, Intrpts, Rcvr_FIFO_trigger_level, RegisterBlock, TX_RX_FIFO,
+    Channel_sts, Control, Intrpts, Rcvr_FIFO_trigger_level, RegisterBlock, TX_RX_FIFO, UART0, UART1,
 };
 
 pub struct Device {
@@ -17,10 +17,21 @@ pub struct Device {
 }
 
 impl Device {
+    /// # Safety
+    ///
+    /// `ptr` must point to a UART register block that is not owned elsewhere.
     pub unsafe fn new(ptr: *mut RegisterBlock) -> Self {
         Self { ptr }
     }
 
+    pub fn from_uart0(uart: UART0) -> Self {
+        Self { ptr: uart.ptr() }
+    }
+
+    pub fn from_uart1(uart: UART1) -> Self {
+        Self { ptr: uart.ptr() }
+    }
+
     fn ptr(&self) -> *mut RegisterBlock {
         self.ptr
     }
@@ -7,6 +7,16 @@
 use tock_registers::registers::{ReadOnly, ReadWrite};
 use tock_registers::{register_bitfields, register_structs};
 
+pub const GEM0_BASE: usize = 0xFF0B_0000;
+pub const GEM1_BASE: usize = 0xFF0C_0000;
+pub const GEM2_BASE: usize = 0xFF0D_0000;
+pub const GEM3_BASE: usize = 0xFF0E_0000;
+
+crate::instance!(GEM0, GEM0_BASE);
+crate::instance!(GEM1, GEM1_BASE);
+crate::instance!(GEM2, GEM2_BASE);
+crate::instance!(GEM3, GEM3_BASE);
+
 register_structs! {
     pub RegisterBlock {
         (0x00 => pub network_control: ReadWrite<u32, network_control::Register>),
@@ -32,6 +42,8 @@ register_structs! {
         (0x50 => _reserved1),
         (0x54 => pub axi_max_pipeline: ReadWrite<u32, axi_max_pipeline::Register>),
         (0x58 => _reserved2),
+        (0x5C => pub int_moderation: ReadWrite<u32, int_moderation::Register>),
+        (0x60 => _reserved2_1),
         (0x80 => pub hash_bottom: ReadWrite<u32, hash_bottom::Register>),
         (0x84 => pub hash_top: ReadWrite<u32, hash_top::Register>),
         (0x88 => pub spec_add1_bottom: ReadWrite<u32, spec_add1_bottom::Register>),
@@ -478,6 +490,10 @@ register_bitfields! {
         AW2W_MAX_PIPELINE OFFSET(8) NUMBITS(8) [],
         AR2R_MAX_PIPELINE OFFSET(0) NUMBITS(8) [],
     ],
+    pub int_moderation [
+        TX_INT_MODERATION OFFSET(16) NUMBITS(8) [],
+        RX_INT_MODERATION OFFSET(0) NUMBITS(8) [],
+    ],
     pub hash_bottom [
         ADDRESS OFFSET(0) NUMBITS(32) [],
     ],
@@ -1165,3 +1181,54 @@ register_bitfields! {
         OFFSET_VALUE OFFSET(0) NUMBITS(7) [],
     ],
 }
+
+// DMA buffer descriptor words. These are not memory mapped registers, but
+// descriptors live in memory shared with the controller and use the same
+// field layout conventions.
+register_bitfields! {
+    u32,
+    pub rx_desc_addr [
+        ADDRESS OFFSET(2) NUMBITS(30) [],
+        WRAP OFFSET(1) NUMBITS(1) [],
+        OWNERSHIP OFFSET(0) NUMBITS(1) [
+            Hardware = 0,
+            Software = 1,
+        ],
+    ],
+    pub rx_desc_status [
+        BROADCAST OFFSET(31) NUMBITS(1) [],
+        MULTICAST_HASH_MATCH OFFSET(30) NUMBITS(1) [],
+        UNICAST_HASH_MATCH OFFSET(29) NUMBITS(1) [],
+        SPEC_ADD_MATCH OFFSET(27) NUMBITS(1) [],
+        SPEC_ADD_REGISTER OFFSET(25) NUMBITS(2) [],
+        TYPE_ID_MATCH OFFSET(24) NUMBITS(1) [],
+        TYPE_ID_REGISTER OFFSET(22) NUMBITS(2) [],
+        // With receive checksum offload enabled bits 24:22 report SNAP and checksum status
+        SNAP_NO_CFI OFFSET(24) NUMBITS(1) [],
+        CHECKSUM OFFSET(22) NUMBITS(2) [
+            NotChecked = 0,
+            IpOk = 1,
+            IpTcpOk = 2,
+            IpUdpOk = 3,
+        ],
+        VLAN_TAG OFFSET(21) NUMBITS(1) [],
+        PRIORITY_TAG OFFSET(20) NUMBITS(1) [],
+        VLAN_PRIORITY OFFSET(17) NUMBITS(3) [],
+        CFI OFFSET(16) NUMBITS(1) [],
+        END_OF_FRAME OFFSET(15) NUMBITS(1) [],
+        START_OF_FRAME OFFSET(14) NUMBITS(1) [],
+        BAD_FCS OFFSET(13) NUMBITS(1) [],
+        LENGTH OFFSET(0) NUMBITS(13) [],
+    ],
+    pub tx_desc_status [
+        USED OFFSET(31) NUMBITS(1) [],
+        WRAP OFFSET(30) NUMBITS(1) [],
+        RETRY_LIMIT_EXCEEDED OFFSET(29) NUMBITS(1) [],
+        AMBA_ERROR OFFSET(27) NUMBITS(1) [],
+        LATE_COLLISION OFFSET(26) NUMBITS(1) [],
+        CHECKSUM_ERROR OFFSET(20) NUMBITS(3) [],
+        NO_CRC OFFSET(16) NUMBITS(1) [],
+        LAST_BUFFER OFFSET(15) NUMBITS(1) [],
+        LENGTH OFFSET(0) NUMBITS(14) [],
+    ],
+}
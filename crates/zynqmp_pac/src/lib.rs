@@ -5,14 +5,98 @@
 //
 
 #![no_std]
-#![recursion_limit = "256"]
+#![recursion_limit = "512"]
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+// Zero sized ownership token for one peripheral instance. Only `Peripherals` creates them, so
+// holding one proves exclusive access to the registers at `PTR`.
+macro_rules! instance {
+    ($name:ident, $base:expr) => {
+        pub struct $name {
+            pub(crate) _private: (),
+        }
+
+        impl $name {
+            pub const PTR: *mut RegisterBlock = $base as *mut RegisterBlock;
+
+            pub fn ptr(&self) -> *mut RegisterBlock {
+                Self::PTR
+            }
+        }
+    };
+}
+pub(crate) use instance;
 
 #[cfg(feature = "ethernet")]
 pub mod gem;
 #[cfg(feature = "uart")]
 pub mod uart;
 
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+#[allow(non_snake_case)]
+pub struct Peripherals {
+    #[cfg(feature = "ethernet")]
+    pub GEM0: gem::GEM0,
+    #[cfg(feature = "ethernet")]
+    pub GEM1: gem::GEM1,
+    #[cfg(feature = "ethernet")]
+    pub GEM2: gem::GEM2,
+    #[cfg(feature = "ethernet")]
+    pub GEM3: gem::GEM3,
+    #[cfg(feature = "uart")]
+    pub UART0: uart::UART0,
+    #[cfg(feature = "uart")]
+    pub UART1: uart::UART1,
+}
+
+impl Peripherals {
+    // Hands out every peripheral instance, only the first call succeeds
+    pub fn take() -> Option<Self> {
+        match TAKEN.swap(true, Ordering::AcqRel) {
+            true => None,
+            // Safety: the flag guarantees this is the only set handed out by `take`
+            false => Some(unsafe { Self::steal() }),
+        }
+    }
+
+    /// # Safety
+    ///
+    /// Creates a second set of tokens, the caller must ensure no instance is used by two
+    /// owners at once.
+    pub unsafe fn steal() -> Self {
+        Self {
+            #[cfg(feature = "ethernet")]
+            GEM0: gem::GEM0 { _private: () },
+            #[cfg(feature = "ethernet")]
+            GEM1: gem::GEM1 { _private: () },
+            #[cfg(feature = "ethernet")]
+            GEM2: gem::GEM2 { _private: () },
+            #[cfg(feature = "ethernet")]
+            GEM3: gem::GEM3 { _private: () },
+            #[cfg(feature = "uart")]
+            UART0: uart::UART0 { _private: () },
+            #[cfg(feature = "uart")]
+            UART1: uart::UART1 { _private: () },
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+
+    #[test]
+    fn register_block_sizes() {
+        assert_eq!(core::mem::size_of::<gem::RegisterBlock>(), 0x720);
+        assert_eq!(core::mem::size_of::<uart::RegisterBlock>(), 0x4C);
+    }
+
+    #[test]
+    fn peripherals_taken_once() {
+        let p = Peripherals::take().unwrap();
+        assert!(Peripherals::take().is_none());
+        assert_eq!(p.GEM3.ptr() as usize, 0xFF0E_0000);
+    }
 }
@@ -7,6 +7,12 @@
 use tock_registers::registers::{ReadOnly, ReadWrite};
 use tock_registers::{register_bitfields, register_structs};
 
+pub const UART0_BASE: usize = 0xFF00_0000;
+pub const UART1_BASE: usize = 0xFF01_0000;
+
+crate::instance!(UART0, UART0_BASE);
+crate::instance!(UART1, UART1_BASE);
+
 register_structs! {
     pub RegisterBlock {
         (0x00 => pub control: ReadWrite<u32, Control::Register>),